@@ -0,0 +1,485 @@
+use super::eval::{evaluate, piece_value};
+use super::zobrist;
+use pleco::core::GenTypes;
+use pleco::{BitMove, Board, PieceType, Player};
+
+use std::cmp::Reverse;
+use std::time::{Duration, SystemTime};
+
+/// The score of a checkmate at the root
+const MATE: i32 = 30_000;
+/// Larger than any score
+const INFINITY: i32 = 32_000;
+/// The maximum depth of the search, including quiescence
+const MAX_PLY: usize = 64;
+/// The number of entries in the transposition table, must be a power of two
+const TABLE_SIZE: usize = 1 << 20;
+/// The number of nodes between checks of the remaining time
+const TIME_CHECK_NODES: usize = 2048;
+
+/// The kind of bound of a stored score
+#[derive(Clone, Copy, PartialEq)]
+enum Bound {
+    /// The score is exact
+    Exact,
+    /// The score is at least this value
+    Lower,
+    /// The score is at most this value
+    Upper,
+}
+
+/// An entry of the transposition table
+#[derive(Clone, Copy)]
+struct TableEntry {
+    key: u64,
+    depth: u8,
+    score: i32,
+    bound: Bound,
+    mv: BitMove,
+}
+
+/// The result of a finished search
+pub struct SearchInfo {
+    /// The best move found
+    pub mv: BitMove,
+    /// The score of the move, in centipawns for the player to move
+    pub score: i32,
+    /// The depth of the last completed iteration
+    pub depth: u8,
+    /// The number of searched nodes
+    pub nodes: usize,
+    /// The principal variation
+    pub pv: Vec<BitMove>,
+}
+
+impl SearchInfo {
+    pub fn info_str(&self) -> String {
+        let score = if self.score.abs() > MATE - MAX_PLY as i32 {
+            // Convert the distance to mate into moves
+            let plies = MATE - self.score.abs();
+            let moves = (plies + 1) / 2;
+            if self.score > 0 {
+                format!("mate in {}", moves)
+            } else {
+                format!("mated in {}", moves)
+            }
+        } else {
+            format!("{:+.2}", self.score as f32 / 100.)
+        };
+        let pv: Vec<String> = self.pv.iter().map(|mv| mv.stringify()).collect();
+        format!(
+            "d:{}, n:{}, {} | pv {}",
+            self.depth,
+            self.nodes,
+            score,
+            pv.join(" ")
+        )
+    }
+}
+
+/// Iterative deepening alpha-beta search
+pub struct AlphaBetaSearch {
+    /// Previously searched positions
+    table: Vec<Option<TableEntry>>,
+    /// Quiet moves that caused a cutoff, by ply
+    killers: [[BitMove; 2]; MAX_PLY],
+    /// How often a quiet move caused a cutoff, by source and destination
+    history: Vec<[i32; 64]>,
+    /// The number of searched nodes
+    nodes: usize,
+    /// The time at which the search has to stop
+    deadline: SystemTime,
+    /// Whether the search ran out of time
+    aborted: bool,
+    /// The maximum depth of the search, if any
+    max_depth: Option<u8>,
+}
+
+impl AlphaBetaSearch {
+    pub fn new(max_depth: Option<u8>) -> AlphaBetaSearch {
+        AlphaBetaSearch {
+            table: vec![None; TABLE_SIZE],
+            killers: [[BitMove::null(); 2]; MAX_PLY],
+            history: vec![[0; 64]; 64],
+            nodes: 0,
+            deadline: SystemTime::now(),
+            aborted: false,
+            max_depth,
+        }
+    }
+
+    /// Searches the best move within the given time, if there are legal moves
+    pub fn search(&mut self, board: &Board, time: Duration) -> Option<SearchInfo> {
+        self.deadline = SystemTime::now() + time;
+        self.aborted = false;
+        self.nodes = 0;
+        self.killers = [[BitMove::null(); 2]; MAX_PLY];
+        for moves in self.history.iter_mut() {
+            *moves = [0; 64];
+        }
+
+        let mut board = board.clone();
+        let first_move = *board.generate_moves().first()?;
+        let mut info = SearchInfo {
+            mv: first_move,
+            score: 0,
+            depth: 0,
+            nodes: 0,
+            pv: vec![first_move],
+        };
+
+        let max_depth = self.max_depth.unwrap_or((MAX_PLY / 2) as u8);
+        for depth in 1..=max_depth {
+            let score = self.negamax(&mut board, depth, 0, -INFINITY, INFINITY);
+            if self.aborted {
+                // The last iteration is incomplete, keep the previous result
+                break;
+            }
+
+            let pv = self.principal_variation(&board, depth);
+            info = SearchInfo {
+                mv: pv.first().cloned().unwrap_or(info.mv),
+                score,
+                depth,
+                nodes: self.nodes,
+                pv,
+            };
+
+            if score.abs() > MATE - MAX_PLY as i32 {
+                // Found a forced mate, searching deeper won't help
+                break;
+            }
+        }
+        info.nodes = self.nodes;
+        Some(info)
+    }
+
+    /// Negamax alpha-beta search, returns the score for the player to move
+    fn negamax(&mut self, board: &mut Board, depth: u8, ply: usize, alpha: i32, beta: i32) -> i32 {
+        let mut alpha = alpha;
+        if self.out_of_time() {
+            return 0;
+        }
+        self.nodes += 1;
+
+        let moves = board.generate_moves();
+        if moves.is_empty() {
+            return if board.in_check() {
+                // Prefer the fastest mate
+                -MATE + ply as i32
+            } else {
+                0
+            };
+        }
+        if ply > 0 && board.rule_50() >= 50 {
+            return 0;
+        }
+        if depth == 0 || ply >= MAX_PLY - 1 {
+            return self.quiescence(board, ply, alpha, beta);
+        }
+
+        // Check for a stored result
+        let key = zobrist::key(board);
+        let entry = self.table[key as usize & (TABLE_SIZE - 1)].filter(|entry| entry.key == key);
+        let table_move = entry.map(|entry| entry.mv).unwrap_or_else(BitMove::null);
+        if let Some(entry) = entry {
+            if ply > 0 && entry.depth >= depth {
+                let score = score_from_table(entry.score, ply);
+                match entry.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower if score >= beta => return score,
+                    Bound::Upper if score <= alpha => return score,
+                    _ => (),
+                }
+            }
+        }
+
+        let original_alpha = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = BitMove::null();
+
+        for mv in self.order_moves(board, moves.iter().cloned().collect(), ply, table_move) {
+            board.apply_move(mv);
+            let score = -self.negamax(board, depth - 1, ply + 1, -beta, -alpha);
+            board.undo_move();
+            if self.aborted {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_move = mv;
+            }
+            if score > alpha {
+                alpha = score;
+            }
+            if alpha >= beta {
+                // Cutoff, remember the quiet move for the ordering
+                if !mv.is_capture() && !mv.is_promo() {
+                    if self.killers[ply][0] != mv {
+                        self.killers[ply][1] = self.killers[ply][0];
+                        self.killers[ply][0] = mv;
+                    }
+                    self.history[mv.get_src().0 as usize][mv.get_dest().0 as usize] +=
+                        depth as i32 * depth as i32;
+                }
+                break;
+            }
+        }
+
+        let bound = if best_score <= original_alpha {
+            Bound::Upper
+        } else if best_score >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.table[key as usize & (TABLE_SIZE - 1)] = Some(TableEntry {
+            key,
+            depth,
+            score: score_to_table(best_score, ply),
+            bound,
+            mv: best_move,
+        });
+
+        best_score
+    }
+
+    /// Searches captures until the position is quiet, to avoid the horizon effect
+    fn quiescence(&mut self, board: &mut Board, ply: usize, alpha: i32, beta: i32) -> i32 {
+        let mut alpha = alpha;
+        if self.out_of_time() {
+            return 0;
+        }
+        self.nodes += 1;
+
+        let in_check = board.in_check();
+        let moves = if in_check {
+            // All evasions have to be considered
+            board.generate_moves()
+        } else {
+            board.generate_moves_of_type(GenTypes::Captures)
+        };
+        if in_check && moves.is_empty() {
+            return -MATE + ply as i32;
+        }
+
+        if !in_check {
+            // Stand pat: The player doesn't have to capture
+            let stand_pat = match board.turn() {
+                Player::White => evaluate(board),
+                Player::Black => -evaluate(board),
+            };
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            if stand_pat > alpha {
+                alpha = stand_pat;
+            }
+        }
+        if ply >= MAX_PLY - 1 {
+            return alpha;
+        }
+
+        for mv in self.order_moves(board, moves.iter().cloned().collect(), ply, BitMove::null()) {
+            // Skip captures that lose material
+            if !in_check && !board.see_ge(mv, 0) {
+                continue;
+            }
+            board.apply_move(mv);
+            let score = -self.quiescence(board, ply + 1, -beta, -alpha);
+            board.undo_move();
+            if self.aborted {
+                return 0;
+            }
+
+            if score >= beta {
+                return score;
+            }
+            if score > alpha {
+                alpha = score;
+            }
+        }
+        alpha
+    }
+
+    /// Sorts the moves so that the most promising moves are searched first
+    fn order_moves(
+        &self,
+        board: &Board,
+        moves: Vec<BitMove>,
+        ply: usize,
+        table_move: BitMove,
+    ) -> Vec<BitMove> {
+        let mut scored: Vec<(i32, BitMove)> = moves
+            .into_iter()
+            .map(|mv| (self.move_order_score(board, mv, ply, table_move), mv))
+            .collect();
+        scored.sort_by_key(|&(score, _)| Reverse(score));
+        scored.into_iter().map(|(_, mv)| mv).collect()
+    }
+
+    fn move_order_score(&self, board: &Board, mv: BitMove, ply: usize, table_move: BitMove) -> i32 {
+        if mv == table_move {
+            // The best move of a previous search
+            3_000_000
+        } else if mv.is_capture() {
+            // Most valuable victim, least valuable attacker
+            let victim = if mv.is_en_passant() {
+                PieceType::P
+            } else {
+                board.captured_piece(mv)
+            };
+            let attacker = board.moved_piece(mv).type_of();
+            2_000_000 + 10 * piece_value(victim) - piece_value(attacker)
+        } else if mv.is_promo() {
+            2_000_000 + piece_value(mv.promo_piece())
+        } else if mv == self.killers[ply][0] {
+            1_000_001
+        } else if mv == self.killers[ply][1] {
+            1_000_000
+        } else {
+            self.history[mv.get_src().0 as usize][mv.get_dest().0 as usize]
+        }
+    }
+
+    /// Follows the best moves in the transposition table
+    fn principal_variation(&self, board: &Board, depth: u8) -> Vec<BitMove> {
+        let mut board = board.clone();
+        let mut pv = Vec::new();
+        for _ in 0..depth {
+            let key = zobrist::key(&board);
+            let entry = match self.table[key as usize & (TABLE_SIZE - 1)] {
+                Some(entry) if entry.key == key && !entry.mv.is_null() => entry,
+                _ => break,
+            };
+            // Guard against hash collisions
+            if !board.generate_moves().contains(&entry.mv) {
+                break;
+            }
+            pv.push(entry.mv);
+            board.apply_move(entry.mv);
+        }
+        pv
+    }
+
+    /// Checks if the search has to stop
+    fn out_of_time(&mut self) -> bool {
+        if !self.aborted
+            && self.nodes.is_multiple_of(TIME_CHECK_NODES)
+            && SystemTime::now() >= self.deadline
+        {
+            self.aborted = true;
+        }
+        self.aborted
+    }
+}
+
+/// Converts a mate score relative to the root into one relative to the stored position
+fn score_to_table(score: i32, ply: usize) -> i32 {
+    if score > MATE - MAX_PLY as i32 {
+        score + ply as i32
+    } else if score < -MATE + MAX_PLY as i32 {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+/// Converts a stored mate score back into one relative to the root
+fn score_from_table(score: i32, ply: usize) -> i32 {
+    if score > MATE - MAX_PLY as i32 {
+        score - ply as i32
+    } else if score < -MATE + MAX_PLY as i32 {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(fen: &str, depth: u8) -> Option<SearchInfo> {
+        let board = Board::from_fen(fen).unwrap();
+        AlphaBetaSearch::new(Some(depth)).search(&board, Duration::from_secs(60))
+    }
+
+    #[test]
+    fn finished_games_have_no_best_move() {
+        // Checkmate and stalemate
+        assert!(search("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1", 3).is_none());
+        assert!(search("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", 3).is_none());
+    }
+
+    #[test]
+    fn mate_in_two_is_found() {
+        // Legal's mate: 1. Nf6+ gxf6 2. Bxf7#
+        let fen = "r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1";
+        let info = search(fen, 4).unwrap();
+        assert_eq!(info.mv.stringify(), "d5f6");
+        assert_eq!(info.score, MATE - 3);
+        assert!(info.info_str().contains("mate in 2"));
+
+        // There is no mate in one, but every reply to the move allows one
+        let mates_in_one = |board: &mut Board| {
+            let mut mates = Vec::new();
+            for mv in board.generate_moves().iter() {
+                board.apply_move(*mv);
+                if board.checkmate() {
+                    mates.push(*mv);
+                }
+                board.undo_move();
+            }
+            mates
+        };
+        let mut board = Board::from_fen(fen).unwrap();
+        assert!(mates_in_one(&mut board).is_empty());
+        board.apply_move(info.mv);
+        for reply in board.generate_moves().iter() {
+            board.apply_move(*reply);
+            assert!(!mates_in_one(&mut board).is_empty(), "{}", reply);
+            board.undo_move();
+        }
+    }
+
+    #[test]
+    fn mate_scores_are_stored_relative_to_the_position() {
+        // A mate in 5 plies from the root, found 2 plies deep, is a mate in 3 plies there
+        assert_eq!(score_to_table(MATE - 5, 2), MATE - 3);
+        assert_eq!(score_to_table(-MATE + 5, 2), -MATE + 3);
+        // Reached again 4 plies deep, it is a mate in 7 plies from the root
+        assert_eq!(score_from_table(MATE - 3, 4), MATE - 7);
+        assert_eq!(score_from_table(-MATE + 3, 4), -MATE + 7);
+
+        for &score in [MATE - 5, -MATE + 5, MATE - 40, 150, -150, 0].iter() {
+            for ply in 0..20 {
+                assert_eq!(score_from_table(score_to_table(score, ply), ply), score);
+            }
+        }
+        // Other scores don't depend on the ply
+        assert_eq!(score_to_table(150, 7), 150);
+        assert_eq!(score_from_table(-150, 7), -150);
+    }
+
+    #[test]
+    fn quiescence_doesnt_stand_pat_in_check() {
+        // Black is ahead, but the knight checks and forks the queen
+        let mut board = Board::from_fen("4q1k1/8/5N2/8/8/8/8/R5K1 b - - 0 1").unwrap();
+        assert!(board.in_check());
+        assert!(-evaluate(&board) > 0);
+
+        let mut search = AlphaBetaSearch::new(None);
+        search.deadline = SystemTime::now() + Duration::from_secs(60);
+        let score = search.quiescence(&mut board, 0, -INFINITY, INFINITY);
+        assert!(score < -300, "{}", score);
+
+        // Without evasions in check it is mate
+        let mut board = Board::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+        assert_eq!(
+            search.quiescence(&mut board, 3, -INFINITY, INFINITY),
+            -MATE + 3
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use super::mcts::{MCTree, MCTreeMove, NodeKey, Proof};
+use super::san::to_san;
+use pleco::Player;
+
+use std::sync::Arc;
+
+/// The number of candidate moves shown
+const CANDIDATES: usize = 3;
+/// The maximum number of plies of the principal variation
+const PV_LENGTH: usize = 8;
+
+/// A move that the search considers, seen from the player to move
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub san: String,
+    /// The number of playouts through the move
+    pub visits: usize,
+    /// The winrate of the player to move after this move, from 0 to 1
+    pub winrate: f32,
+}
+
+/// A snapshot of what the search thinks about a position, shown while it runs
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    /// The position that was analyzed
+    pub key: NodeKey,
+    /// The probability that white wins, from 0 to 1
+    pub white_winrate: f32,
+    pub playouts: usize,
+    /// The most promising moves, the move that would be played first
+    pub candidates: Vec<Candidate>,
+    /// The expected continuation in standard algebraic notation
+    pub pv: Vec<String>,
+    pub proof: Option<Proof>,
+}
+
+impl Analysis {
+    /// Takes a snapshot of the search tree
+    pub fn of(root: &MCTree) -> Analysis {
+        // The winrate of the player to move
+        let winrate = match root.proof {
+            Some(Proof::Win(_)) => 1.,
+            Some(Proof::Loss(_)) => 0.,
+            Some(Proof::Draw) => 0.5,
+            None => 1. - root.play_value(),
+        };
+        let white_winrate = match root.player() {
+            Player::White => winrate,
+            Player::Black => 1. - winrate,
+        };
+
+        let mut moves: Vec<&MCTreeMove> = root
+            .children
+            .iter()
+            .filter(|mv_node| mv_node.playouts > 0)
+            .collect();
+        moves.sort_by(|a, b| b.cmp_play_value(a));
+        let candidates = moves
+            .iter()
+            .take(CANDIDATES)
+            .map(|mv_node| Candidate {
+                san: to_san(&root.state, mv_node.mv),
+                visits: mv_node.playouts,
+                winrate: mv_node.play_value(),
+            })
+            .collect();
+
+        Analysis {
+            key: root.key(),
+            white_winrate,
+            playouts: root.playouts,
+            candidates,
+            pv: principal_variation(root),
+            proof: root.proof,
+        }
+    }
+
+    /// The summary line, like `White 56.3% | 1200 playouts | mate in 3`
+    pub fn summary_str(&self) -> String {
+        let s = format!(
+            "White {:.1}% | {} playouts",
+            self.white_winrate * 100.,
+            self.playouts
+        );
+        match self.proof {
+            Some(proof) => format!("{} | {}", s, proof.describe()),
+            None => s,
+        }
+    }
+}
+
+/// Follows the best moves from the root as long as they have been searched
+fn principal_variation(root: &MCTree) -> Vec<String> {
+    let mut pv = Vec::new();
+    let mut board = root.state.parallel_clone();
+    let mut next = root
+        .best_move()
+        .filter(|mv_node| mv_node.playouts > 0)
+        .map(|mv_node| (mv_node.mv, Arc::clone(&mv_node.node)));
+
+    while let Some((mv, node)) = next {
+        pv.push(to_san(&board, mv));
+        board.apply_move(mv);
+        if pv.len() >= PV_LENGTH {
+            break;
+        }
+        next = node
+            .lock()
+            .unwrap()
+            .best_move()
+            .filter(|mv_node| mv_node.playouts > 0)
+            .map(|mv_node| (mv_node.mv, Arc::clone(&mv_node.node)));
+    }
+    pv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcts::{MCTreeRef, SearchSettings, TranspositionTable};
+    use pleco::Board;
+
+    /// Searches the position until the root is proven or the playouts run out
+    fn search(fen: &str) -> MCTreeRef {
+        let mut table = TranspositionTable::new();
+        let root = table.get_or_insert(&Board::from_fen(fen).unwrap());
+        let settings = SearchSettings::default();
+        for _ in 0..200 {
+            let mut root = root.lock().unwrap();
+            if root.proof.is_some() {
+                break;
+            }
+            root.select(&mut table, &settings);
+        }
+        root
+    }
+
+    fn analyze(fen: &str) -> Analysis {
+        Analysis::of(&search(fen).lock().unwrap())
+    }
+
+    fn analysis(white_winrate: f32, playouts: usize, proof: Option<Proof>) -> Analysis {
+        Analysis {
+            key: (0, 0, 0),
+            white_winrate,
+            playouts,
+            candidates: Vec::new(),
+            pv: Vec::new(),
+            proof,
+        }
+    }
+
+    #[test]
+    fn summaries_show_the_winrate_of_white_and_the_proof() {
+        assert_eq!(
+            analysis(0.5634, 1200, None).summary_str(),
+            "White 56.3% | 1200 playouts"
+        );
+        assert_eq!(
+            analysis(1., 64, Some(Proof::Win(5))).summary_str(),
+            "White 100.0% | 64 playouts | mate in 3"
+        );
+        assert_eq!(
+            analysis(0., 8, Some(Proof::Loss(2))).summary_str(),
+            "White 0.0% | 8 playouts | mated in 1"
+        );
+        assert_eq!(
+            analysis(0.5, 0, Some(Proof::Draw)).summary_str(),
+            "White 50.0% | 0 playouts | proven draw"
+        );
+    }
+
+    #[test]
+    fn mates_are_the_first_candidate_and_the_pv() {
+        let analysis = analyze("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        assert_eq!(analysis.proof, Some(Proof::Win(1)));
+        assert_eq!(analysis.white_winrate, 1.);
+        assert_eq!(analysis.candidates[0].san, "Ra8#");
+        assert_eq!(analysis.pv, vec!["Ra8#"]);
+        assert!(analysis.summary_str().ends_with("| mate in 1"));
+
+        // From the side of black, white still wins
+        let analysis = analyze("r5k1/8/8/8/8/8/5PPP/6K1 b - - 0 1");
+        assert_eq!(analysis.proof, Some(Proof::Win(1)));
+        assert_eq!(analysis.white_winrate, 0.);
+        assert_eq!(analysis.pv, vec!["Ra1#"]);
+    }
+
+    #[test]
+    fn candidates_are_the_most_played_moves() {
+        let root = search("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        let root = root.lock().unwrap();
+        let analysis = Analysis::of(&root);
+        assert!(analysis.candidates.len() <= CANDIDATES);
+        assert!(!analysis.candidates.is_empty());
+        assert!(analysis.pv.len() <= PV_LENGTH);
+        // The candidates are sorted, and the principal variation starts with the best move
+        assert!(analysis
+            .candidates
+            .windows(2)
+            .all(|pair| pair[0].winrate >= pair[1].winrate));
+        let best = root.best_move().unwrap();
+        assert_eq!(analysis.pv[0], to_san(&root.state, best.mv));
+        assert_eq!(analysis.candidates[0].winrate, best.play_value());
+        assert!(analysis
+            .candidates
+            .iter()
+            .all(|candidate| candidate.visits > 0 && (0. ..=1.).contains(&candidate.winrate)));
+    }
+}
@@ -0,0 +1,226 @@
+use super::match_runner::number_option;
+use super::pgn::{parse_games, PgnGame};
+use super::player_spec::option_value;
+use super::polyglot::{encode_move, BookEntry, PolyglotKeys};
+use pleco::Player;
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+/// The number of games a move needs without the `--min-count` option
+const DEFAULT_MIN_COUNT: usize = 3;
+/// The number of half moves stored without the `--max-ply` option
+const DEFAULT_MAX_PLY: usize = 40;
+
+/// How often a move was played and how it scored for the moving player
+#[derive(Debug, Clone, Copy, Default)]
+struct MoveStats {
+    games: usize,
+    wins: usize,
+    draws: usize,
+}
+
+impl MoveStats {
+    /// The weight of the move in the book, two points for a win and one for a draw like in Polyglot
+    fn points(&self) -> usize {
+        2 * self.wins + self.draws
+    }
+}
+
+/// Aggregates the moves of games into an opening book
+pub struct BookBuilder {
+    keys: PolyglotKeys,
+    /// The number of half moves of each game that are added
+    max_ply: usize,
+    /// The rating the moving player needs, if any
+    min_elo: Option<usize>,
+    /// The statistics by position key and encoded move
+    stats: HashMap<(u64, u16), MoveStats>,
+    pub games_added: usize,
+}
+
+impl BookBuilder {
+    pub fn new(keys: PolyglotKeys, max_ply: usize, min_elo: Option<usize>) -> BookBuilder {
+        BookBuilder {
+            keys,
+            max_ply,
+            min_elo,
+            stats: HashMap::new(),
+            games_added: 0,
+        }
+    }
+
+    /// Checks if the player is rated high enough to learn from
+    fn rated_enough(&self, game: &PgnGame, player: Player) -> bool {
+        let tag = match player {
+            Player::White => "WhiteElo",
+            Player::Black => "BlackElo",
+        };
+        match self.min_elo {
+            Some(min_elo) => game
+                .tag(tag)
+                .and_then(|elo| elo.parse::<usize>().ok())
+                .is_some_and(|elo| elo >= min_elo),
+            None => true,
+        }
+    }
+
+    /// Adds the opening moves of the game, returns false for games without a result
+    pub fn add_game(&mut self, game: &PgnGame) -> bool {
+        let result = match game.result {
+            Some(result) => result,
+            None => return false,
+        };
+        self.games_added += 1;
+
+        let mut board = game.start.clone();
+        for mv in game.moves.iter().take(self.max_ply) {
+            let player = board.turn();
+            if self.rated_enough(game, player) {
+                let stats = self
+                    .stats
+                    .entry((self.keys.key(&board), encode_move(*mv)))
+                    .or_default();
+                stats.games += 1;
+                match result.score(player) {
+                    score if score > 0.75 => stats.wins += 1,
+                    score if score > 0.25 => stats.draws += 1,
+                    _ => (),
+                }
+            }
+            board.apply_move(*mv);
+        }
+        true
+    }
+
+    /// The entries of the book, sorted by key.
+    ///
+    /// Moves played less often than `min_count` or that never scored are left out.
+    pub fn entries(&self, min_count: usize) -> Vec<BookEntry> {
+        let kept: Vec<(&(u64, u16), &MoveStats)> = self
+            .stats
+            .iter()
+            .filter(|(_, stats)| stats.games >= min_count && stats.points() > 0)
+            .collect();
+        // Scale the weights down if they don't fit
+        let max_points = kept
+            .iter()
+            .map(|(_, stats)| stats.points())
+            .max()
+            .unwrap_or(0);
+        let scale = if max_points > u16::MAX as usize {
+            u16::MAX as f64 / max_points as f64
+        } else {
+            1.
+        };
+
+        let mut entries: Vec<BookEntry> = kept
+            .iter()
+            .map(|(&(key, mv), stats)| BookEntry {
+                key,
+                mv,
+                weight: ((stats.points() as f64 * scale) as u16).max(1),
+            })
+            .collect();
+        entries.sort_by_key(|entry| (entry.key, std::cmp::Reverse(entry.weight), entry.mv));
+        entries
+    }
+}
+
+/// Builds a book from PGN files, like
+/// `build-book book.bin games.pgn --min-count 5 --min-elo 2200`
+pub fn run_book_builder(args: &[String]) -> Result<(), String> {
+    let usage = "Usage: build-book <book.bin> <games.pgn>... [--min-count n] [--min-elo n] \
+                 [--max-ply n] [--book-keys file]";
+    let paths: Vec<&String> = args
+        .iter()
+        .skip(2)
+        .take_while(|arg| !arg.starts_with("--"))
+        .collect();
+    if paths.len() < 2 {
+        return Err(String::from(usage));
+    }
+    let option_err = |err: String| format!("{}\n{}", err, usage);
+    let min_count = number_option(args, "--min-count", DEFAULT_MIN_COUNT).map_err(option_err)?;
+    let max_ply = number_option(args, "--max-ply", DEFAULT_MAX_PLY).map_err(option_err)?;
+    let min_elo = match option_value(args, "--min-elo") {
+        Some(_) => Some(number_option(args, "--min-elo", 0).map_err(option_err)?),
+        None => None,
+    };
+    let keys = match option_value(args, "--book-keys") {
+        Some(path) => PolyglotKeys::load(path)?,
+        None => PolyglotKeys::standard(),
+    };
+
+    let mut builder = BookBuilder::new(keys, max_ply, min_elo);
+    let mut skipped = 0;
+    for path in paths[1..].iter() {
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("Can't read the games '{}': {}", path, err))?;
+        for game in parse_games(&text) {
+            match game {
+                Ok(game) => {
+                    if !builder.add_game(&game) {
+                        skipped += 1;
+                    }
+                }
+                Err(err) => {
+                    println!("Skipped a game of {}: {}", path, err);
+                    skipped += 1;
+                }
+            }
+        }
+    }
+
+    let entries = builder.entries(min_count);
+    let positions: HashSet<u64> = entries.iter().map(|entry| entry.key).collect();
+    let bytes: Vec<u8> = entries.iter().flat_map(|entry| entry.to_bytes()).collect();
+    fs::write(paths[0], bytes)
+        .map_err(|err| format!("Can't write the book '{}': {}", paths[0], err))?;
+    println!(
+        "Added {} games ({} skipped): {} moves in {} positions saved to {}",
+        builder.games_added,
+        skipped,
+        entries.len(),
+        positions.len(),
+        paths[0]
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::polyglot::{Book, BookSelection};
+    use std::env;
+
+    #[test]
+    fn written_books_can_be_probed() {
+        let pgn = "[Event \"Test\"]\n[Result \"1-0\"]\n\n\
+                   1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. O-O Nf6 1-0\n";
+        let game = parse_games(pgn).remove(0).unwrap();
+        let mut builder = BookBuilder::new(PolyglotKeys::standard(), DEFAULT_MAX_PLY, None);
+        assert!(builder.add_game(&game));
+
+        let entries = builder.entries(1);
+        // Only the moves of the winner scored, the first one from the reference start key
+        assert_eq!(entries.len(), 4);
+        assert!(entries.iter().any(|entry| entry.key == 0x463b96181691fc9c));
+
+        let path = env::temp_dir().join(format!("cli-chess-book-{}.bin", std::process::id()));
+        let bytes: Vec<u8> = entries.iter().flat_map(|entry| entry.to_bytes()).collect();
+        fs::write(&path, bytes).unwrap();
+        let book = Book::load(path.to_str().unwrap(), PolyglotKeys::standard());
+        fs::remove_file(&path).unwrap();
+        let book = book.unwrap();
+
+        // Every move of the game is found again, including castling
+        let mut board = game.start.clone();
+        for mv in game.moves.iter() {
+            if board.turn() == Player::White {
+                assert_eq!(book.choose_move(&board, BookSelection::Best), Some(*mv));
+            }
+            board.apply_move(*mv);
+        }
+    }
+}
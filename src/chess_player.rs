@@ -1,163 +1,555 @@
-use super::mcts::{MCTree, MCTreeMove};
-use pleco::{BitMove, Board, MoveList, Player};
-use rand::{self, rngs::ThreadRng, Rng};
-use std::cmp::Ordering;
-use std::io;
-use std::io::BufRead;
-use std::sync::mpsc::channel;
-use std::sync::{mpsc, Arc, Mutex};
-use std::thread;
-use std::time::{Duration, SystemTime};
-
-pub trait ChessPlayer {
-    fn next_move(&mut self, board: &Board, time: Duration) -> BitMove;
-    fn ponder(&mut self, board: &Board);
-}
-
-pub struct HumanPlayer {}
-
-impl HumanPlayer {
-    pub fn new() -> HumanPlayer {
-        HumanPlayer {}
-    }
-}
-
-impl ChessPlayer for HumanPlayer {
-    fn next_move(&mut self, board: &Board, time: Duration) -> BitMove {
-        let stdin = io::stdin();
-
-        loop {
-            let uci_move = stdin.lock().lines().next().unwrap().unwrap();
-
-            let all_moves: MoveList = board.generate_moves();
-            let bit_move: Option<BitMove> = all_moves
-                .iter()
-                .find(|m| m.stringify() == uci_move)
-                .cloned();
-            if let Some(mov) = bit_move {
-                return mov;
-            } else {
-                println!("Invalid move. Try again:");
-            }
-        }
-    }
-
-    fn ponder(&mut self, board: &Board) {
-        thread::sleep(Duration::from_millis(500));
-    }
-}
-
-pub struct RandomPlayer {}
-
-impl RandomPlayer {
-    pub fn new() -> RandomPlayer {
-        RandomPlayer {}
-    }
-}
-
-impl ChessPlayer for RandomPlayer {
-    fn next_move(&mut self, board: &Board, time: Duration) -> BitMove {
-        let all_moves: MoveList = board.generate_moves();
-        let mut rng = rand::thread_rng();
-        let rnd = rng.gen_range(0 as usize, all_moves.len());
-        let mv = all_moves[rnd];
-
-        thread::sleep(time);
-        mv
-    }
-
-    fn ponder(&mut self, board: &Board) {
-        thread::sleep(Duration::from_millis(500));
-    }
-}
-
-pub struct StoneFish {
-    player: Player,
-    root: MCTree,
-}
-
-impl StoneFish {
-    pub fn new(player: Player, board: &Board) -> StoneFish {
-        StoneFish {
-            player: player,
-            root: MCTree::new(board),
-        }
-    }
-
-    /// Tries to apply the given move to the root node
-    fn apply_root_move(&mut self, apply_move: BitMove) -> bool {
-        for _ in 0..self.root.children.len() {
-            let mv_node = self.root.children.pop().unwrap();
-            let mv = mv_node.mv;
-            if apply_move == mv {
-                // Found appropriate move
-                self.root = mv_node.node;
-                let result = self.root.size();
-                println!("{} nodes saved.", result);
-                return true;
-            }
-        }
-        return false;
-    }
-
-    /// Updates the root node for the new situation
-    fn update_root(&mut self, board: &Board) {
-        if *board == self.root.state {
-            // The root is already up-to-date
-            return;
-        } else {
-            let last_mv_opt = board.last_move();
-
-            match last_mv_opt {
-                Option::Some(last_mv) => {
-                    // Check if the last move can be applied
-                    let result = self.apply_root_move(last_mv);
-                    if !result {
-                        panic!("Last move can't be applied!");
-                    } else {
-                        return;
-                    }
-                }
-                Option::None => panic!("No board move found, but board not up-to-date!"),
-            }
-        }
-    }
-}
-
-impl ChessPlayer for StoneFish {
-    fn next_move(&mut self, board: &Board, time: Duration) -> BitMove {
-        let now = SystemTime::now();
-
-        assert_eq!(self.player, board.turn(), "Can't move for the opponent!");
-
-        // Update root state
-        self.update_root(board);
-        assert_eq!(*board, self.root.state, "False move board!");
-        assert_eq!(board.turn(), self.root.player(), "Root player not move player!");
-
-        // Calculate while time is remaining
-        while now.elapsed().unwrap() < time {
-            self.root.select();
-        }
-
-        println!("{}", self.root.info_str());
-
-        self.root.assert_valid();
-
-        // Select move to play
-        let mv_node = self.root.best_move().unwrap();
-        let mv = mv_node.mv;
-
-        self.apply_root_move(mv);
-
-        mv
-    }
-
-    fn ponder(&mut self, board: &Board) {
-        self.update_root(board);
-        assert_eq!(*board, self.root.state, "False ponder board!");
-        assert_ne!(self.player, board.turn(), "Must ponder on the opponent's move!");
-        assert_eq!(board.turn(), self.root.player(), "Root player not pondering player!");
-        self.root.select();
-    }
-}
+use super::alpha_beta::AlphaBetaSearch;
+use super::analysis::Analysis;
+use super::cli_board::{terminal_width, CliBoard, Overlay};
+use super::endgame::Endgame;
+use super::line_editor::LineEditor;
+use super::mcts::{MCTree, MCTreeRef, SearchSettings, TranspositionTable};
+use super::move_input::{self, parse_square};
+use super::polyglot::{Book, BookSelection};
+use super::san::to_san;
+use super::tui;
+use pleco::bot_prelude::{
+    AlphaBetaSearcher, IterativeSearcher, JamboreeSearcher, MiniMaxSearcher,
+    ParallelMiniMaxSearcher, RandomBot, Searcher,
+};
+use pleco::{BitMove, Board, MoveList, Player};
+use rand::{self, Rng};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// How often a running search updates the analysis on the display
+const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+/// The commands of the human player, completed like moves
+const COMMANDS: [&str; 7] = [
+    "/flip", "/moves", "/show", "/attacks", "/hanging", "/clear", "/peek",
+];
+
+pub trait ChessPlayer {
+    /// Chooses the move to play, a null move resigns the game
+    fn next_move(&mut self, board: &Board, time: Duration) -> BitMove;
+    fn ponder(&mut self, board: &Board);
+
+    /// Enables or disables printing search information, like in engine matches
+    fn set_verbose(&mut self, _verbose: bool) {}
+
+    /// Gives the player the board shown to the user, for commands and the engine analysis
+    fn set_display(&mut self, _display: Arc<Mutex<CliBoard>>) {}
+}
+
+pub struct HumanPlayer {
+    display: Option<Arc<Mutex<CliBoard>>>,
+    /// Reads the moves and keeps the history of the input
+    editor: LineEditor,
+}
+
+impl HumanPlayer {
+    pub fn new() -> HumanPlayer {
+        HumanPlayer {
+            display: None,
+            editor: LineEditor::new(),
+        }
+    }
+}
+
+impl HumanPlayer {
+    /// The words that tab completes: the legal moves in UCI and SAN and the commands
+    fn completions(board: &Board) -> Vec<String> {
+        let mut words: Vec<String> = board
+            .generate_moves()
+            .iter()
+            .flat_map(|mv| vec![mv.stringify(), to_san(board, *mv)])
+            .chain(COMMANDS.iter().map(|command| command.to_string()))
+            .collect();
+        words.sort();
+        words.dedup();
+        words
+    }
+
+    /// Runs a command like `/flip`, which changes the view instead of making a move
+    fn run_command(&self, command: &str) {
+        let display = match self.display {
+            Some(ref display) => display,
+            None => {
+                println!("There is no board to change.");
+                return;
+            }
+        };
+        let mut display = display.lock().unwrap();
+        let words: Vec<&str> = command.split_whitespace().collect();
+        let overlay = match words.as_slice() {
+            ["/flip"] => {
+                // Turn the board around and show it again
+                display.flip();
+                display.color_print();
+                return;
+            }
+            ["/moves"] => {
+                println!("{}", display.move_list_str(terminal_width()));
+                return;
+            }
+            ["/peek"] => {
+                if display.blindfold().is_none() {
+                    println!("Nothing is hidden.");
+                    return;
+                }
+                // Show the position until the next move
+                display.peek();
+                display.color_print();
+                return;
+            }
+            ["/show", square] => match parse_square(square) {
+                Some(sq) => Some(Overlay::Moves(sq)),
+                None => {
+                    println!("Invalid square '{}', use one like e2.", square);
+                    return;
+                }
+            },
+            // The squares of the opponent by default
+            ["/attacks"] => Some(Overlay::Attacks(!display.turn())),
+            ["/attacks", "white"] => Some(Overlay::Attacks(Player::White)),
+            ["/attacks", "black"] => Some(Overlay::Attacks(Player::Black)),
+            ["/hanging"] => Some(Overlay::Hanging),
+            ["/clear"] => None,
+            _ => {
+                println!(
+                    "Unknown command '{}'. Use /flip, /moves, /show e2, \
+                     /attacks [white|black], /hanging, /clear or /peek.",
+                    command
+                );
+                return;
+            }
+        };
+        display.set_overlay(overlay);
+        display.color_print();
+        if let Some(overlay) = overlay {
+            println!("{}", overlay.describe());
+        }
+    }
+}
+
+impl ChessPlayer for HumanPlayer {
+    fn next_move(&mut self, board: &Board, _time: Duration) -> BitMove {
+        if let Some(ref display) = self.display {
+            if display.lock().unwrap().is_full_screen() {
+                return tui::select_move(display, board);
+            }
+        }
+        let words = HumanPlayer::completions(board);
+        let prompt = format!("{}> ", board.turn());
+
+        loop {
+            let uci_move = match self.editor.read_line(&prompt, &words) {
+                Some(line) => line,
+                None => {
+                    println!("The input ended, so the game is over.");
+                    return BitMove::null();
+                }
+            };
+            // The input moved the cursor below the board
+            if let Some(ref display) = self.display {
+                display.lock().unwrap().mark_output();
+            }
+
+            if uci_move.starts_with('/') {
+                self.run_command(uci_move.trim());
+                continue;
+            }
+
+            match move_input::parse_move(board, &uci_move) {
+                Ok(mv) => return mv,
+                Err(reason) => {
+                    if let Some(ref display) = self.display {
+                        display.lock().unwrap().count_illegal_move();
+                    }
+                    println!("{}\nTry again:", reason)
+                }
+            }
+        }
+    }
+
+    fn ponder(&mut self, _board: &Board) {
+        thread::sleep(Duration::from_millis(500));
+    }
+
+    fn set_display(&mut self, display: Arc<Mutex<CliBoard>>) {
+        self.display = Some(display);
+    }
+}
+
+pub struct RandomPlayer {}
+
+impl RandomPlayer {
+    pub fn new() -> RandomPlayer {
+        RandomPlayer {}
+    }
+}
+
+impl ChessPlayer for RandomPlayer {
+    fn next_move(&mut self, board: &Board, time: Duration) -> BitMove {
+        let all_moves: MoveList = board.generate_moves();
+        let mut rng = rand::thread_rng();
+        let rnd = rng.gen_range(0, all_moves.len());
+        let mv = all_moves[rnd];
+
+        thread::sleep(time);
+        mv
+    }
+
+    fn ponder(&mut self, _board: &Board) {
+        thread::sleep(Duration::from_millis(500));
+    }
+}
+
+pub struct StoneFish {
+    player: Player,
+    root: MCTreeRef,
+    table: TranspositionTable,
+    settings: SearchSettings,
+    /// The search for the mate of basic endgames, created when it is needed
+    mating_search: Option<AlphaBetaSearch>,
+    verbose: bool,
+    /// The board shown to the user, which gets the analysis while searching
+    display: Option<Arc<Mutex<CliBoard>>>,
+    last_refresh: SystemTime,
+}
+
+impl StoneFish {
+    pub fn new(player: Player, board: &Board, settings: SearchSettings) -> StoneFish {
+        let mut table = TranspositionTable::new();
+        let root = table.get_or_insert(board);
+        StoneFish {
+            player,
+            root,
+            table,
+            settings,
+            mating_search: None,
+            verbose: true,
+            display: None,
+            last_refresh: SystemTime::now(),
+        }
+    }
+
+    /// Shows the state of the search on the display
+    fn show_analysis(display: &Option<Arc<Mutex<CliBoard>>>, root: &MCTree) {
+        if let Some(ref display) = display {
+            display.lock().unwrap().show_analysis(Analysis::of(root));
+        }
+    }
+
+    /// Tries to apply the last move of the given board to the root node
+    fn apply_root_move(&mut self, board: &Board) -> bool {
+        let apply_move = match board.last_move() {
+            Option::Some(last_mv) => last_mv,
+            Option::None => return false,
+        };
+        let next_node = self
+            .root
+            .lock()
+            .unwrap()
+            .children
+            .iter()
+            .find(|mv_node| mv_node.mv == apply_move)
+            .map(|mv_node| Arc::clone(&mv_node.node));
+
+        match next_node {
+            Option::Some(node) => {
+                // Found appropriate move
+                self.root = node;
+                self.table.prune();
+                let mut root = self.root.lock().unwrap();
+                // The node might have been reached by a different move order
+                root.state = board.clone();
+                // The analysis panel shows the saved playouts instead
+                if self.verbose && self.display.is_none() {
+                    println!("{} nodes saved.", root.size());
+                }
+                true
+            }
+            Option::None => false,
+        }
+    }
+
+    /// Moves the root along a move that was found without the tree
+    fn play_without_search(&mut self, board: &Board, mv: BitMove) -> BitMove {
+        let mut next_board = board.clone();
+        next_board.apply_move(mv);
+        self.apply_root_move(&next_board);
+        mv
+    }
+
+    /// Updates the root node for the new situation
+    fn update_root(&mut self, board: &Board) {
+        if *board == self.root.lock().unwrap().state {
+            // The root is already up-to-date
+            return;
+        }
+
+        if board.last_move().is_none() {
+            panic!("No board move found, but board not up-to-date!");
+        }
+        // Check if the last move can be applied
+        if !self.apply_root_move(board) {
+            // The move hasn't been searched yet, start from scratch
+            self.root = self.table.get_or_insert(board);
+            self.table.prune();
+            self.root.lock().unwrap().state = board.clone();
+        }
+    }
+}
+
+impl ChessPlayer for StoneFish {
+    fn next_move(&mut self, board: &Board, time: Duration) -> BitMove {
+        let now = SystemTime::now();
+
+        assert_eq!(self.player, board.turn(), "Can't move for the opponent!");
+
+        // Update root state
+        self.update_root(board);
+
+        // Random playouts rarely find the mate of a basic endgame, a mating search does
+        if let Some((endgame, strong)) = Endgame::recognize(board) {
+            if !endgame.is_draw() && strong == self.player {
+                let search = self
+                    .mating_search
+                    .get_or_insert_with(|| AlphaBetaSearch::new(None));
+                if let Some(info) = search.search(board, time) {
+                    if self.verbose {
+                        println!("{} mating search: {}", endgame.name(), info.info_str());
+                    }
+                    return self.play_without_search(board, info.mv);
+                }
+            }
+        }
+
+        let mut root = self.root.lock().unwrap();
+        assert_eq!(*board, root.state, "False move board!");
+        assert_eq!(board.turn(), root.player(), "Root player not move player!");
+
+        // Calculate while time is remaining and the result is unknown
+        while root.proof.is_none() && now.elapsed().unwrap() < time {
+            root.select(&mut self.table, &self.settings);
+            if self.last_refresh.elapsed().unwrap_or_default() >= REFRESH_INTERVAL {
+                self.last_refresh = SystemTime::now();
+                StoneFish::show_analysis(&self.display, &root);
+            }
+        }
+
+        if self.verbose {
+            println!("{}", root.info_str(&self.table));
+            println!(
+                "{}",
+                self.settings
+                    .playout_stats
+                    .info_str(&*self.settings.playouts)
+            );
+            if let Some(ref display) = self.display {
+                display.lock().unwrap().mark_output();
+            }
+        }
+
+        root.assert_valid();
+
+        // Select move to play
+        let mv_node = root.best_move().unwrap();
+        let mv = mv_node.mv;
+        drop(root);
+
+        let mut next_board = board.clone();
+        next_board.apply_move(mv);
+        self.apply_root_move(&next_board);
+
+        mv
+    }
+
+    fn ponder(&mut self, board: &Board) {
+        self.update_root(board);
+        let mut root = self.root.lock().unwrap();
+        assert_eq!(*board, root.state, "False ponder board!");
+        assert_ne!(
+            self.player,
+            board.turn(),
+            "Must ponder on the opponent's move!"
+        );
+        assert_eq!(
+            board.turn(),
+            root.player(),
+            "Root player not pondering player!"
+        );
+        if root.proof.is_some() {
+            // Nothing left to calculate
+            drop(root);
+            thread::sleep(Duration::from_millis(100));
+        } else {
+            root.select(&mut self.table, &self.settings);
+            if self.last_refresh.elapsed().unwrap_or_default() >= REFRESH_INTERVAL {
+                self.last_refresh = SystemTime::now();
+                StoneFish::show_analysis(&self.display, &root);
+            }
+        }
+    }
+
+    fn set_verbose(&mut self, verbose: bool) {
+        self.verbose = verbose;
+    }
+
+    fn set_display(&mut self, display: Arc<Mutex<CliBoard>>) {
+        display.lock().unwrap().enable_analysis();
+        self.display = Some(display);
+    }
+}
+
+pub struct AlphaBetaPlayer {
+    search: AlphaBetaSearch,
+    verbose: bool,
+}
+
+impl AlphaBetaPlayer {
+    pub fn new(max_depth: Option<u8>) -> AlphaBetaPlayer {
+        AlphaBetaPlayer {
+            search: AlphaBetaSearch::new(max_depth),
+            verbose: true,
+        }
+    }
+}
+
+impl ChessPlayer for AlphaBetaPlayer {
+    fn next_move(&mut self, board: &Board, time: Duration) -> BitMove {
+        match self.search.search(board, time) {
+            Some(info) => {
+                if self.verbose {
+                    println!("{}", info.info_str());
+                }
+                info.mv
+            }
+            // The game is over, there is nothing to play
+            None => BitMove::null(),
+        }
+    }
+
+    fn ponder(&mut self, _board: &Board) {
+        thread::sleep(Duration::from_millis(500));
+    }
+
+    fn set_verbose(&mut self, verbose: bool) {
+        self.verbose = verbose;
+    }
+}
+
+/// The searchers that ship with pleco
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlecoBot {
+    Random,
+    MiniMax,
+    ParallelMiniMax,
+    AlphaBeta,
+    Jamboree,
+    Iterative,
+}
+
+impl PlecoBot {
+    /// Gets the bot with the given name
+    pub fn from_name(name: &str) -> Option<PlecoBot> {
+        match name {
+            "random" => Some(PlecoBot::Random),
+            "minimax" => Some(PlecoBot::MiniMax),
+            "parallel-minimax" => Some(PlecoBot::ParallelMiniMax),
+            "alphabeta" => Some(PlecoBot::AlphaBeta),
+            "jamboree" => Some(PlecoBot::Jamboree),
+            "iterative" => Some(PlecoBot::Iterative),
+            _ => None,
+        }
+    }
+
+    /// Searches the best move with the given depth
+    pub fn best_move(self, board: Board, depth: u16) -> BitMove {
+        match self {
+            PlecoBot::Random => RandomBot::best_move(board, depth),
+            PlecoBot::MiniMax => MiniMaxSearcher::best_move(board, depth),
+            PlecoBot::ParallelMiniMax => ParallelMiniMaxSearcher::best_move(board, depth),
+            PlecoBot::AlphaBeta => AlphaBetaSearcher::best_move(board, depth),
+            PlecoBot::Jamboree => JamboreeSearcher::best_move(board, depth),
+            PlecoBot::Iterative => IterativeSearcher::best_move(board, depth),
+        }
+    }
+}
+
+/// Plays with one of the searchers of pleco at a fixed depth
+pub struct PlecoBotPlayer {
+    bot: PlecoBot,
+    depth: u16,
+}
+
+impl PlecoBotPlayer {
+    pub fn new(bot: PlecoBot, depth: u16) -> PlecoBotPlayer {
+        PlecoBotPlayer { bot, depth }
+    }
+}
+
+impl ChessPlayer for PlecoBotPlayer {
+    fn next_move(&mut self, board: &Board, _time: Duration) -> BitMove {
+        // The bots search to a fixed depth instead of using the time
+        self.bot.best_move(board.clone(), self.depth)
+    }
+
+    fn ponder(&mut self, _board: &Board) {
+        thread::sleep(Duration::from_millis(500));
+    }
+}
+
+/// Plays from an opening book and lets the inner player take over when out of book
+pub struct BookPlayer {
+    book: Book,
+    inner: Box<dyn ChessPlayer + Send>,
+    selection: BookSelection,
+    /// The number of half moves after which the book isn't used anymore
+    max_depth: Option<u16>,
+    verbose: bool,
+}
+
+impl BookPlayer {
+    pub fn new(
+        book: Book,
+        inner: Box<dyn ChessPlayer + Send>,
+        selection: BookSelection,
+        max_depth: Option<u16>,
+    ) -> BookPlayer {
+        BookPlayer {
+            book,
+            inner,
+            selection,
+            max_depth,
+            verbose: true,
+        }
+    }
+}
+
+impl ChessPlayer for BookPlayer {
+    fn next_move(&mut self, board: &Board, time: Duration) -> BitMove {
+        let in_depth = self
+            .max_depth
+            .is_none_or(|max_depth| board.moves_played() < max_depth);
+        if in_depth {
+            if let Some(mv) = self.book.choose_move(board, self.selection) {
+                if self.verbose {
+                    println!("Book move {}", mv.stringify());
+                }
+                return mv;
+            }
+        }
+        self.inner.next_move(board, time)
+    }
+
+    fn ponder(&mut self, board: &Board) {
+        self.inner.ponder(board);
+    }
+
+    fn set_verbose(&mut self, verbose: bool) {
+        self.verbose = verbose;
+        self.inner.set_verbose(verbose);
+    }
+
+    fn set_display(&mut self, display: Arc<Mutex<CliBoard>>) {
+        self.inner.set_display(display);
+    }
+}
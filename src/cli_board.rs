@@ -1,346 +1,1101 @@
-use colored::*;
-use pleco::{BitMove, Board, MoveList, Piece, Player, SQ};
-
-const HEIGHT: u8 = 10;
-const HISTORY_START: u8 = 2;
-
-pub enum DrawType {
-    Stalemate,
-    Rule50,
-}
-
-pub enum BoardState {
-    Win(Player),
-    Draw(DrawType),
-    Turn(Player),
-}
-
-pub enum CliSquareColor {
-    White,
-    Black,
-}
-
-pub struct CliSquare {
-    rank: u8,
-    row: u8,
-    color: CliSquareColor,
-}
-
-pub struct CliMove {
-    src: SQ,
-    dest: SQ,
-    piece: Piece,
-    player: Player,
-    capture: Option<Piece>,
-    capture_sq: Option<SQ>,
-    promo: Option<Piece>,
-    check_sq: Option<SQ>,
-}
-
-impl CliMove {
-    pub fn new(mv: BitMove, board: Board) -> CliMove {
-        let src = mv.get_src();
-        let dest = mv.get_dest();
-        let piece = board.piece_at_sq(src);
-        let player = board.turn();
-        let check_sq = if board.gives_check(mv) {
-            match player {
-                Player::White => Some(board.king_sq(Player::Black)),
-                Player::Black => Some(board.king_sq(Player::White)),
-            }
-        } else {
-            None
-        };
-        let capture_sq = if mv.is_capture() {
-            if mv.is_en_passant() {
-                match player {
-                    Player::White => {
-                        let SQ(sq) = dest;
-                        Option::Some(SQ(sq - 8))
-                    }
-                    Player::Black => {
-                        let SQ(sq) = dest;
-                        Option::Some(SQ(sq + 8))
-                    }
-                }
-            } else {
-                Option::Some(dest)
-            }
-        } else {
-            Option::None
-        };
-        let capture = match capture_sq {
-            Option::Some(sq) => Option::Some(board.piece_at_sq(sq)),
-            Option::None => Option::None,
-        };
-
-        let promo = if mv.is_promo() {
-            Option::Some(Piece::make_lossy(player, mv.promo_piece()))
-        } else {
-            Option::None
-        };
-
-        CliMove {
-            src: src,
-            dest: dest,
-            piece: piece,
-            player: player,
-            capture_sq: capture_sq,
-            capture: capture,
-            promo: promo,
-            check_sq: check_sq,
-        }
-    }
-
-    pub fn color_str(&self) -> String {
-        // Add default info
-        let piece_str = CliMove::piece_str(self.piece);
-        let src_str = self.src.to_string();
-        let dest_str = self.dest.to_string();
-        let mut s = format!("{} {} -> {}", piece_str, src_str, dest_str);
-        match self.promo {
-            Option::None => (),
-            Option::Some(promo) => s = format!("{} {}", s, CliMove::piece_str(promo)),
-        };
-        // Add capture info if applicable
-        match self.capture {
-            Option::None => (),
-            Option::Some(capture) => {
-                let cap_str = format! {"{} ", capture.character_lossy()};
-                let cap_str = match capture.player_lossy() {
-                    Player::White => cap_str.black().on_bright_red().to_string(),
-                    Player::Black => cap_str.white().on_red().to_string(),
-                };
-                s = format!("{} {}", s, cap_str);
-            }
-        }
-        // Add check info if applicable
-        match self.check_sq {
-            Option::None => (),
-            Option::Some(_) => {
-                let king_str = match self.player {
-                    // White player moved, black player in check
-                    Player::White => "k ".black().on_yellow(),
-                    // Black player moved, white player in check
-                    Player::Black => "K ".black().on_bright_yellow(),
-                };
-                s = format!("{} {}", s, king_str);
-            }
-        }
-        s
-    }
-
-    pub fn piece_str(piece: Piece) -> String {
-        let piece_str = piece.character_lossy().to_string() + " ";
-        match piece.player_lossy() {
-            Player::White => piece_str.black().on_white().to_string(),
-            Player::Black => piece_str.white().on_black().to_string(),
-        }
-    }
-}
-
-pub struct CliBoard {
-    board: Board,          // The board to display
-    history: Vec<CliMove>, // The moves played so far
-}
-
-impl CliBoard {
-    pub fn new(board: Board) -> CliBoard {
-        CliBoard {
-            board: board,
-            history: Vec::new(),
-        }
-    }
-
-    pub fn from_fen(fen_str: &str) -> Result<CliBoard, &str> {
-        if let Ok(board) = Board::from_fen(fen_str) {
-            Result::Ok(CliBoard::new(board))
-        } else {
-            Result::Err("Invalid fen string!")
-        }
-    }
-
-    pub fn apply_uci_move(&mut self, uci_move: &str) -> bool {
-        let board = self.board.clone();
-        let result = self.board.apply_uci_move(uci_move);
-        if result {
-            let cli_mv = CliMove::new(self.board.last_move().unwrap(), board);
-            self.history.push(cli_mv);
-            result
-        } else {
-            result
-        }
-    }
-
-    pub fn turn(&self) -> Player {
-        self.board.turn()
-    }
-
-    pub fn board(&self) -> Board {
-        self.board.clone()
-    }
-
-    pub fn apply_move(&mut self, bit_move: BitMove) {
-        let board = self.board.clone();
-        self.board.apply_move(bit_move);
-        let cli_mv = CliMove::new(bit_move, board);
-        self.history.push(cli_mv);
-    }
-
-    pub fn generate_moves(&self) -> MoveList {
-        self.board.generate_moves()
-    }
-
-    pub fn color_print(&self) {
-        println!("{}", self.color_string());
-    }
-
-    pub fn color_string(&self) -> String {
-        let mut s = format!("{}  {}\n", CliBoard::file_header(), self.board_state_str());
-        for rev_rank in 0..8 {
-            let rank = 8 - rev_rank;
-            s += &format!(
-                "{} {} {}  {}\n",
-                rank,
-                self.color_rank_string(rank),
-                rank,
-                self.history_str(rank)
-            );
-        }
-        s += &CliBoard::file_header();
-        s
-    }
-
-    fn board_state_str(&self) -> String {
-        let rule_50 = self.board.rule_50();
-        match self.board_state() {
-            BoardState::Win(Player::White) => String::from("White won!"),
-            BoardState::Win(Player::Black) => String::from("Black won!"),
-            BoardState::Turn(Player::White) => format!("White to move. ({}/50)", rule_50),
-            BoardState::Turn(Player::Black) => format!("Black to move. ({}/50)", rule_50),
-            BoardState::Draw(DrawType::Stalemate) => String::from("It's a draw (stalemate)."),
-            BoardState::Draw(DrawType::Rule50) => String::from("It's a draw (rule 50)."),
-        }
-    }
-
-    pub fn board_state(&self) -> BoardState {
-        if self.board.rule_50() >= 50 {
-            BoardState::Draw(DrawType::Rule50)
-        } else if self.board.stalemate() {
-            BoardState::Draw(DrawType::Stalemate)
-        } else if self.board().checkmate() {
-            if self.turn() == Player::White {
-                BoardState::Win(Player::Black)
-            } else {
-                BoardState::Win(Player::White)
-            }
-        } else {
-            if self.turn() == Player::White {
-                BoardState::Turn(Player::White)
-            } else {
-                BoardState::Turn(Player::Black)
-            }
-        }
-    }
-
-    fn history_str(&self, row: u8) -> String {
-        let rev_index = (HEIGHT - row - HISTORY_START) as usize;
-        if rev_index >= self.history.len() {
-            String::new()
-        } else {
-            let index = self.history.len() - rev_index - 1;
-            format!("{:03}: {}", index + 1, self.history[index].color_str())
-        }
-    }
-
-    fn file_header() -> String {
-        let s = String::from("  a b c d e f g h  ");
-        s
-    }
-
-    fn color_rank_string(&self, rank: u8) -> String {
-        if rank < 1 || rank > 8 {
-            panic!("Rank out of bounds");
-        }
-
-        let mut s = String::new();
-        for file in 1..9 {
-            s += &self.color_square_string(rank, file);
-        }
-        s
-    }
-
-    fn color_square_string(&self, rank: u8, file: u8) -> String {
-        if rank < 1 || rank > 8 {
-            panic!("Rank out of bounds");
-        }
-        if file < 1 || file > 8 {
-            panic!("File out of bounds");
-        }
-
-        let square = SQ((rank - 1) * 8 + file - 1);
-        let last_mv = self.history.last();
-        let is_capture = match last_mv {
-            None => false,
-            Some(mv) => {
-                if let Some(cap_sq) = mv.capture_sq {
-                    cap_sq == square
-                } else {
-                    false
-                }
-            }
-        };
-        let has_changed = match last_mv {
-            None => false,
-            Some(mv) => {
-                let src = mv.src;
-                let dest = mv.dest;
-                src == square || dest == square
-            }
-        };
-
-        let piece = self.board.get_piece_locations().piece_at(square);
-
-        let piece_str = if piece != Piece::None {
-            piece.character_lossy().to_string() + " "
-        } else {
-            String::from("  ")
-        };
-
-        let is_white = square.on_light_square();
-
-        let is_in_check = if let Some(mv) = last_mv {
-            if let Some(sq) = mv.check_sq {
-                sq == square
-            } else {
-                false
-            }
-        } else {
-            false
-        };
-
-        let color_string = if is_white {
-            if is_in_check {
-                piece_str.black().on_bright_yellow()
-            } else if is_capture {
-                piece_str.black().on_bright_red()
-            } else if has_changed {
-                piece_str.black().on_bright_blue()
-            } else {
-                piece_str.black().on_white()
-            }
-        } else {
-            if is_in_check {
-                piece_str.black().on_yellow()
-            } else if is_capture {
-                piece_str.white().on_red()
-            } else if has_changed {
-                piece_str.white().on_blue()
-            } else {
-                piece_str.white().on_black()
-            }
-        };
-
-        color_string.to_string()
-    }
-}
+use super::analysis::Analysis;
+use super::eval::piece_value;
+use super::mcts::MCTree;
+use super::san::to_san;
+use super::theme::{Highlight, Theme};
+use super::tui::{Selection, PROMOTIONS};
+use pleco::{BitMove, Board, MoveList, Piece, PieceType, Player, SQ};
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::time::{Duration, SystemTime};
+
+/// The number of move pairs listed next to the board, one per rank
+const HISTORY_ROWS: usize = 8;
+/// The terminal width assumed if neither the terminal nor `COLUMNS` tell it
+const DEFAULT_WIDTH: usize = 80;
+/// The number of candidate moves in the analysis panel
+const CANDIDATE_ROWS: usize = 3;
+/// The screen row of the eighth rank in the full-screen mode, starting at 1
+const BOARD_ROW: u16 = 2;
+/// The screen column of the a-file, starting at 1
+const BOARD_COLUMN: u16 = 3;
+/// The screen row and column of the promotion popup
+const POPUP_ROW: u16 = 5;
+const POPUP_COLUMN: u16 = 4;
+
+/// Extra information drawn on the board, for learning and analyzing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Overlay {
+    /// The legal destinations of the piece on the square
+    Moves(SQ),
+    /// The squares attacked by the player
+    Attacks(Player),
+    /// The pieces of both players that are attacked and not defended
+    Hanging,
+}
+
+impl Overlay {
+    pub fn describe(self) -> String {
+        match self {
+            Overlay::Moves(sq) => format!("Showing the moves of {}.", sq),
+            Overlay::Attacks(player) => format!("Showing the squares attacked by {}.", player),
+            Overlay::Hanging => String::from("Showing the hanging pieces."),
+        }
+    }
+}
+
+/// What the board hides, for training to play without seeing the position
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Blindfold {
+    /// The board is empty
+    AllPieces,
+    /// The pieces of the given player are hidden
+    Pieces(Player),
+    /// Only the moves are shown, without the board
+    MoveList,
+}
+
+pub enum DrawType {
+    Stalemate,
+    Rule50,
+}
+
+pub enum BoardState {
+    Win(Player),
+    Draw(DrawType),
+    Turn(Player),
+}
+
+impl BoardState {
+    /// Determines the state of the given board
+    pub fn of(board: &Board) -> BoardState {
+        if board.rule_50() >= 50 {
+            BoardState::Draw(DrawType::Rule50)
+        } else if board.stalemate() {
+            BoardState::Draw(DrawType::Stalemate)
+        } else if board.checkmate() {
+            BoardState::Win(!board.turn())
+        } else {
+            BoardState::Turn(board.turn())
+        }
+    }
+}
+
+#[allow(dead_code)]
+pub enum CliSquareColor {
+    White,
+    Black,
+}
+
+#[allow(dead_code)]
+pub struct CliSquare {
+    rank: u8,
+    row: u8,
+    color: CliSquareColor,
+}
+
+pub struct CliMove {
+    src: SQ,
+    dest: SQ,
+    piece: Piece,
+    player: Player,
+    capture: Option<Piece>,
+    capture_sq: Option<SQ>,
+    promo: Option<Piece>,
+    check_sq: Option<SQ>,
+    san: String,
+    number: u16, // The full move number
+}
+
+impl CliMove {
+    pub fn new(mv: BitMove, board: Board) -> CliMove {
+        let san = to_san(&board, mv);
+        let number = board.moves_played() / 2 + 1;
+        let src = mv.get_src();
+        let dest = mv.get_dest();
+        let piece = board.piece_at_sq(src);
+        let player = board.turn();
+        let check_sq = if board.gives_check(mv) {
+            match player {
+                Player::White => Some(board.king_sq(Player::Black)),
+                Player::Black => Some(board.king_sq(Player::White)),
+            }
+        } else {
+            None
+        };
+        let capture_sq = if mv.is_capture() {
+            if mv.is_en_passant() {
+                match player {
+                    Player::White => {
+                        let SQ(sq) = dest;
+                        Option::Some(SQ(sq - 8))
+                    }
+                    Player::Black => {
+                        let SQ(sq) = dest;
+                        Option::Some(SQ(sq + 8))
+                    }
+                }
+            } else {
+                Option::Some(dest)
+            }
+        } else {
+            Option::None
+        };
+        let capture = capture_sq.map(|sq| board.piece_at_sq(sq));
+
+        let promo = if mv.is_promo() {
+            Option::Some(Piece::make_lossy(player, mv.promo_piece()))
+        } else {
+            Option::None
+        };
+
+        CliMove {
+            src,
+            dest,
+            piece,
+            player,
+            capture_sq,
+            capture,
+            promo,
+            check_sq,
+            san,
+            number,
+        }
+    }
+
+    pub fn color_str(&self, theme: &Theme) -> String {
+        // Add default info
+        let piece_str = theme.piece_str(self.piece);
+        let src_str = self.src.to_string();
+        let dest_str = self.dest.to_string();
+        let mut s = format!("{} {} -> {}", piece_str, src_str, dest_str);
+        match self.promo {
+            Option::None => (),
+            Option::Some(promo) => s = format!("{} {}", s, theme.piece_str(promo)),
+        };
+        // Add capture info if applicable
+        match self.capture {
+            Option::None => (),
+            Option::Some(capture) => {
+                s = format!("{} {}", s, theme.capture_str(capture));
+            }
+        }
+        // Add check info if applicable
+        match self.check_sq {
+            Option::None => (),
+            Option::Some(_) => {
+                // The opponent of the moving player is in check
+                let king = Piece::make_lossy(!self.player, PieceType::K);
+                s = format!("{} {}", s, theme.check_str(king));
+            }
+        }
+        s
+    }
+}
+
+pub struct CliBoard {
+    board: Board,                 // The board to display
+    history: Vec<CliMove>,        // The moves played so far
+    flipped: bool,                // Whether black is shown at the bottom
+    theme: Theme,                 // The colors and piece symbols
+    analysis_panel: bool,         // Whether an engine shows its analysis
+    analysis: Option<Analysis>,   // The latest analysis of a running search
+    live_lines: Option<usize>,    // The height of the board at the end of the output
+    full_screen: bool,            // Whether the board fills the terminal
+    selection: Option<Selection>, // The squares of the move being chosen
+    message: String,              // The help line below the full-screen board
+    clock: [Duration; 2],         // The thinking time of white and black
+    turn_start: SystemTime,       // When the player to move started thinking
+    overlay: Option<Overlay>,     // The extra information drawn on the board
+    blindfold: Option<Blindfold>, // What the board hides for training
+    peeking: bool,                // Whether the hidden position is shown
+    peeks: usize,                 // How often the position was revealed
+    illegal_moves: usize,         // How often a human tried an illegal move
+}
+
+impl CliBoard {
+    pub fn new(board: Board) -> CliBoard {
+        CliBoard {
+            board,
+            history: Vec::new(),
+            flipped: false,
+            theme: Theme::default(),
+            analysis_panel: false,
+            analysis: None,
+            live_lines: None,
+            full_screen: false,
+            selection: None,
+            message: String::new(),
+            clock: [Duration::from_secs(0); 2],
+            turn_start: SystemTime::now(),
+            overlay: None,
+            blindfold: None,
+            peeking: false,
+            peeks: 0,
+            illegal_moves: 0,
+        }
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    /// Shows the board from the side of the given player
+    pub fn set_perspective(&mut self, player: Player) {
+        self.flipped = player == Player::Black;
+    }
+
+    /// Turns the board around
+    pub fn flip(&mut self) {
+        self.flipped = !self.flipped;
+        if self.full_screen {
+            self.redraw();
+        }
+    }
+
+    pub fn is_flipped(&self) -> bool {
+        self.flipped
+    }
+
+    /// Draws the board on the whole terminal instead of printing it below the output
+    pub fn set_full_screen(&mut self, full_screen: bool) {
+        self.full_screen = full_screen;
+    }
+
+    pub fn is_full_screen(&self) -> bool {
+        self.full_screen
+    }
+
+    /// Shows the move being chosen on the full screen
+    pub fn set_selection(&mut self, selection: Option<Selection>) {
+        if self.selection != selection {
+            self.selection = selection;
+            self.redraw();
+        }
+    }
+
+    /// Draws extra information on the board, or nothing extra
+    pub fn set_overlay(&mut self, overlay: Option<Overlay>) {
+        self.overlay = overlay;
+        if self.full_screen {
+            self.redraw();
+        }
+    }
+
+    pub fn overlay(&self) -> Option<Overlay> {
+        self.overlay
+    }
+
+    /// Hides the pieces or the whole board, or shows everything again
+    pub fn set_blindfold(&mut self, blindfold: Option<Blindfold>) {
+        self.blindfold = blindfold;
+    }
+
+    pub fn blindfold(&self) -> Option<Blindfold> {
+        self.blindfold
+    }
+
+    /// Shows the hidden position until the next move
+    pub fn peek(&mut self) {
+        if self.blindfold.is_some() && !self.peeking {
+            self.peeking = true;
+            self.peeks += 1;
+        }
+    }
+
+    /// Counts a move of a human that was rejected
+    pub fn count_illegal_move(&mut self) {
+        self.illegal_moves += 1;
+    }
+
+    /// How often the player peeked and tried illegal moves, if the board was hidden
+    pub fn blindfold_stats_str(&self) -> Option<String> {
+        self.blindfold?;
+        Some(format!(
+            "Blindfold: {} peek{}, {} illegal move attempt{}.",
+            self.peeks,
+            if self.peeks == 1 { "" } else { "s" },
+            self.illegal_moves,
+            if self.illegal_moves == 1 { "" } else { "s" }
+        ))
+    }
+
+    /// Sets the help line below the full-screen board
+    pub fn set_message(&mut self, message: &str) {
+        self.message = String::from(message);
+    }
+
+    /// Draws the whole screen again, for the running clock and the analysis
+    pub fn redraw(&self) {
+        if !self.full_screen {
+            return;
+        }
+        let mut screen = String::from("\x1b[H");
+        for line in self.color_string().lines() {
+            screen += &format!("{}\x1b[K\n", line);
+        }
+        screen += &format!("\n  {}\x1b[K\n\x1b[J", self.message);
+        if let Some(index) = self.selection.as_ref().and_then(|sel| sel.promotion) {
+            screen += &self.popup_str(index);
+        }
+        let mut stdout = io::stdout().lock();
+        let _ = stdout.write_all(screen.as_bytes());
+        let _ = stdout.flush();
+    }
+
+    /// The promotion popup over the board, with the chosen piece in reverse video
+    fn popup_str(&self, index: usize) -> String {
+        let pieces: Vec<String> = PROMOTIONS
+            .iter()
+            .enumerate()
+            .map(|(i, piece_type)| {
+                let piece = self
+                    .theme
+                    .piece_char(Piece::make_lossy(self.board.turn(), *piece_type));
+                if i == index {
+                    format!("\x1b[7m{}\x1b[0m", piece)
+                } else {
+                    piece.to_string()
+                }
+            })
+            .collect();
+        let border = "+---------+";
+        format!(
+            "\x1b[{};{col}H{border}\x1b[{};{col}H| {} |\x1b[{};{col}H{border}",
+            POPUP_ROW,
+            POPUP_ROW + 1,
+            pieces.join(" "),
+            POPUP_ROW + 2,
+            col = POPUP_COLUMN,
+            border = border,
+        )
+    }
+
+    /// The square at a screen position of the full-screen mode
+    pub fn square_at(&self, column: u16, row: u16) -> Option<SQ> {
+        let rev_row = row.checked_sub(BOARD_ROW)?;
+        let file = column.checked_sub(BOARD_COLUMN)? / 2;
+        if rev_row >= 8 || file >= 8 {
+            return None;
+        }
+        let (rank, file) = if self.flipped {
+            (rev_row, 7 - file)
+        } else {
+            (7 - rev_row, file)
+        };
+        Some(SQ((rank * 8 + file) as u8))
+    }
+
+    /// The index of the promotion piece at a screen position, while the popup is open
+    pub fn promotion_at(&self, column: u16, row: u16) -> Option<usize> {
+        if row != POPUP_ROW + 1 {
+            return None;
+        }
+        let index = column.checked_sub(POPUP_COLUMN + 2)? as usize / 2;
+        if index < PROMOTIONS.len() {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    /// Reserves space for the analysis of an engine next to and below the board.
+    ///
+    /// The analysis is only shown live on a terminal, so other outputs don't get the panel.
+    pub fn enable_analysis(&mut self) {
+        self.analysis_panel = io::stdout().is_terminal();
+    }
+
+    /// Shows the analysis of a running search.
+    ///
+    /// On a terminal the board is redrawn in place, as long as nothing was printed below it.
+    pub fn show_analysis(&mut self, analysis: Analysis) {
+        self.analysis = Some(analysis);
+        let lines = match self.live_lines {
+            // The full screen is redrawn regularly anyway
+            Some(lines) if self.analysis_panel && !self.full_screen => lines,
+            _ => return,
+        };
+        let s = self.color_string();
+        let mut stdout = io::stdout().lock();
+        // Keep the cursor where it is, the user might be typing a move
+        let mut redraw = format!("\x1b7\x1b[{}F", lines);
+        for line in s.lines() {
+            redraw += &format!("{}\x1b[K\n", line);
+        }
+        redraw += "\x1b8";
+        let _ = stdout.write_all(redraw.as_bytes());
+        let _ = stdout.flush();
+    }
+
+    /// Notes that something was printed below the board, so it isn't redrawn in place
+    pub fn mark_output(&mut self) {
+        self.live_lines = None;
+    }
+
+    #[allow(dead_code)]
+    pub fn from_fen(fen_str: &str) -> Result<CliBoard, &str> {
+        if let Ok(board) = Board::from_fen(fen_str) {
+            Result::Ok(CliBoard::new(board))
+        } else {
+            Result::Err("Invalid fen string!")
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn apply_uci_move(&mut self, uci_move: &str) -> bool {
+        let board = self.board.clone();
+        let result = self.board.apply_uci_move(uci_move);
+        if result {
+            self.stop_clock(board.turn());
+            let cli_mv = CliMove::new(self.board.last_move().unwrap(), board);
+            self.history.push(cli_mv);
+            result
+        } else {
+            result
+        }
+    }
+
+    pub fn turn(&self) -> Player {
+        self.board.turn()
+    }
+
+    pub fn board(&self) -> Board {
+        self.board.clone()
+    }
+
+    pub fn apply_move(&mut self, bit_move: BitMove) {
+        self.stop_clock(self.board.turn());
+        // The piece has moved or is gone
+        if let Some(Overlay::Moves(_)) = self.overlay {
+            self.overlay = None;
+        }
+        self.peeking = false;
+        let board = self.board.clone();
+        self.board.apply_move(bit_move);
+        let cli_mv = CliMove::new(bit_move, board);
+        self.history.push(cli_mv);
+    }
+
+    /// Adds the time since the last move to the clock of the player who moved
+    fn stop_clock(&mut self, player: Player) {
+        self.clock[player as usize] += self.turn_start.elapsed().unwrap_or_default();
+        self.turn_start = SystemTime::now();
+    }
+
+    /// The thinking time of both players, like `White 01:23 | Black 00:45`
+    fn clock_str(&self) -> String {
+        let mut clock = self.clock;
+        if let BoardState::Turn(player) = self.board_state() {
+            clock[player as usize] += self.turn_start.elapsed().unwrap_or_default();
+        }
+        let time = |duration: Duration| {
+            format!(
+                "{:02}:{:02}",
+                duration.as_secs() / 60,
+                duration.as_secs() % 60
+            )
+        };
+        format!(
+            "White {} | Black {}",
+            time(clock[Player::White as usize]),
+            time(clock[Player::Black as usize])
+        )
+    }
+
+    #[allow(dead_code)]
+    pub fn generate_moves(&self) -> MoveList {
+        self.board.generate_moves()
+    }
+
+    pub fn color_print(&mut self) {
+        if self.full_screen {
+            self.redraw();
+            return;
+        }
+        let s = self.color_string();
+        println!("{}", s);
+        self.live_lines = Some(s.lines().count());
+    }
+
+    pub fn color_string(&self) -> String {
+        if self.blindfold == Some(Blindfold::MoveList) && !self.peeking {
+            return self.move_list_view();
+        }
+        let pairs = self.move_pairs();
+        // Only the latest moves fit next to the board
+        let shown = &pairs[pairs.len().saturating_sub(HISTORY_ROWS)..];
+
+        let analysis = self.current_analysis();
+
+        let mut s = format!("{}  {}", self.file_header(), self.board_state_str());
+        if self.full_screen {
+            s += &format!("  {}", self.clock_str());
+        }
+        s.push('\n');
+        for rev_row in 0..8 {
+            let row = 8 - rev_row;
+            let rank = if self.flipped { rev_row + 1 } else { row };
+            let bar = match analysis {
+                Some(analysis) => format!("{} ", self.bar_cell(analysis, rev_row)),
+                None if self.analysis_panel => String::from("   "),
+                None => String::new(),
+            };
+            s += &format!(
+                "{} {} {}  {}{}\n",
+                rank,
+                self.color_rank_string(rank),
+                rank,
+                bar,
+                shown.get(rev_row as usize).map_or("", String::as_str)
+            );
+        }
+        s += &self.file_header();
+        if let Some(last_mv) = self.history.last() {
+            s += &format!("  Last move: {}", last_mv.color_str(&self.theme));
+            s += &self.captures_str();
+        }
+        if self.analysis_panel {
+            s += &self.analysis_str(analysis);
+        }
+        s
+    }
+
+    /// The state of the game and all moves, instead of the board
+    fn move_list_view(&self) -> String {
+        let mut s = format!(
+            "{}\n{}",
+            self.board_state_str(),
+            self.move_list_str(terminal_width())
+        );
+        if self.full_screen {
+            s += &format!("\n{}", self.clock_str());
+        }
+        if let Some(last_mv) = self.history.last() {
+            s += &format!("\n  Last move: {}", last_mv.color_str(&self.theme));
+            s += &self.captures_str();
+        }
+        if self.analysis_panel {
+            s += &self.analysis_str(self.current_analysis());
+        }
+        s
+    }
+
+    /// The analysis of the position on the board, if the search has one
+    fn current_analysis(&self) -> Option<&Analysis> {
+        self.analysis
+            .as_ref()
+            .filter(|analysis| analysis.key == MCTree::state_key(&self.board))
+    }
+
+    /// A cell of the evaluation bar next to the board, which fills up from the side of white
+    fn bar_cell(&self, analysis: &Analysis, rev_row: u8) -> String {
+        let white_cells = (analysis.white_winrate * 8.).round() as u8;
+        let row_from_white = if self.flipped { rev_row } else { 7 - rev_row };
+        self.theme.bar_str(row_from_white < white_cells)
+    }
+
+    /// The winrate, the candidate moves and the principal variation, always in the same lines
+    fn analysis_str(&self, analysis: Option<&Analysis>) -> String {
+        let mut s = format!(
+            "\n  Engine: {}",
+            analysis.map_or(String::from("waiting"), Analysis::summary_str)
+        );
+        for row in 0..CANDIDATE_ROWS {
+            s.push('\n');
+            if let Some(candidate) = analysis.and_then(|analysis| analysis.candidates.get(row)) {
+                s += &format!(
+                    "    {:<8}{:>8} ({:.1}%)",
+                    candidate.san,
+                    candidate.visits,
+                    candidate.winrate * 100.
+                );
+            }
+        }
+        s += &format!(
+            "\n  PV: {}",
+            analysis.map_or(String::new(), |analysis| analysis.pv.join(" "))
+        );
+        s
+    }
+
+    /// The pieces of the opponent that the player has captured, the most valuable first
+    pub fn captured_by(&self, player: Player) -> Vec<Piece> {
+        let mut captured: Vec<Piece> = self
+            .history
+            .iter()
+            .filter(|mv| mv.player == player)
+            .filter_map(|mv| mv.capture)
+            .collect();
+        captured.sort_by_key(|piece| -piece_value(piece.type_of()));
+        captured
+    }
+
+    /// The material of white minus the material of black in pawns, as traded in the game
+    pub fn material_balance(&self) -> i32 {
+        let pawn = piece_value(PieceType::P);
+        self.history
+            .iter()
+            .map(|mv| {
+                let captured = mv.capture.map_or(0, |piece| piece_value(piece.type_of()));
+                // A promoted pawn gains the difference to the new piece
+                let promoted = mv
+                    .promo
+                    .map_or(0, |piece| piece_value(piece.type_of()) - pawn);
+                let gain = (captured + promoted) / pawn;
+                match mv.player {
+                    Player::White => gain,
+                    Player::Black => -gain,
+                }
+            })
+            .sum()
+    }
+
+    /// The captured pieces of both players with the material difference, like `Nbp +3`
+    fn captures_str(&self) -> String {
+        let balance = self.material_balance();
+        let mut s = String::new();
+        for &player in [Player::White, Player::Black].iter() {
+            let pieces: String = self
+                .captured_by(player)
+                .into_iter()
+                .map(|piece| self.theme.piece_char(piece))
+                .collect();
+            let lead = match player {
+                Player::White => balance,
+                Player::Black => -balance,
+            };
+            let line = if lead > 0 {
+                format!("{} +{}", pieces, lead)
+            } else {
+                pieces
+            };
+            s += format!("\n  {} captured: {}", player, line.trim_start()).trim_end();
+        }
+        s
+    }
+
+    /// The moves in pairs of white and black, like `12. Nf3 Nc6`
+    pub fn move_pairs(&self) -> Vec<String> {
+        let mut pairs: Vec<String> = Vec::new();
+        for mv in &self.history {
+            match (mv.player, pairs.last_mut()) {
+                (Player::White, _) => pairs.push(format!("{}. {}", mv.number, mv.san)),
+                (Player::Black, Some(pair)) => *pair += &format!(" {}", mv.san),
+                // The game started with a move of black
+                (Player::Black, None) => pairs.push(format!("{}... {}", mv.number, mv.san)),
+            }
+        }
+        pairs
+    }
+
+    /// Lists all moves in as many columns as fit into the width, read from top to bottom
+    pub fn move_list_str(&self, width: usize) -> String {
+        let pairs = self.move_pairs();
+        if pairs.is_empty() {
+            return String::from("No moves yet.");
+        }
+        let column_width = pairs.iter().map(String::len).max().unwrap_or(0) + 3;
+        let columns = (width / column_width).max(1);
+        let rows = pairs.len().div_ceil(columns);
+
+        let mut s = String::new();
+        for row in 0..rows {
+            let line: String = (0..columns)
+                .filter_map(|column| pairs.get(column * rows + row))
+                .map(|pair| format!("{:<width$}", pair, width = column_width))
+                .collect();
+            s += line.trim_end();
+            s.push('\n');
+        }
+        s.pop();
+        s
+    }
+
+    fn board_state_str(&self) -> String {
+        let rule_50 = self.board.rule_50();
+        match self.board_state() {
+            BoardState::Win(Player::White) => String::from("White won!"),
+            BoardState::Win(Player::Black) => String::from("Black won!"),
+            BoardState::Turn(Player::White) => format!("White to move. ({}/50)", rule_50),
+            BoardState::Turn(Player::Black) => format!("Black to move. ({}/50)", rule_50),
+            BoardState::Draw(DrawType::Stalemate) => String::from("It's a draw (stalemate)."),
+            BoardState::Draw(DrawType::Rule50) => String::from("It's a draw (rule 50)."),
+        }
+    }
+
+    pub fn board_state(&self) -> BoardState {
+        BoardState::of(&self.board)
+    }
+
+    fn file_header(&self) -> String {
+        if self.flipped {
+            String::from("  h g f e d c b a  ")
+        } else {
+            String::from("  a b c d e f g h  ")
+        }
+    }
+
+    fn color_rank_string(&self, rank: u8) -> String {
+        if !(1..=8).contains(&rank) {
+            panic!("Rank out of bounds");
+        }
+
+        let mut s = String::new();
+        for file in 1..9 {
+            let file = if self.flipped { 9 - file } else { file };
+            s += &self.color_square_string(rank, file);
+        }
+        s
+    }
+
+    fn color_square_string(&self, rank: u8, file: u8) -> String {
+        if !(1..=8).contains(&rank) {
+            panic!("Rank out of bounds");
+        }
+        if !(1..=8).contains(&file) {
+            panic!("File out of bounds");
+        }
+
+        let square = SQ((rank - 1) * 8 + file - 1);
+        let last_mv = self.history.last();
+        let is_capture = match last_mv {
+            None => false,
+            Some(mv) => {
+                if let Some(cap_sq) = mv.capture_sq {
+                    cap_sq == square
+                } else {
+                    false
+                }
+            }
+        };
+        let has_changed = match last_mv {
+            None => false,
+            Some(mv) => {
+                let src = mv.src;
+                let dest = mv.dest;
+                src == square || dest == square
+            }
+        };
+
+        let mut piece = self.board.get_piece_locations().piece_at(square);
+        if self.hides(piece) {
+            piece = Piece::None;
+        }
+
+        let is_in_check = if let Some(mv) = last_mv {
+            if let Some(sq) = mv.check_sq {
+                sq == square
+            } else {
+                false
+            }
+        } else {
+            false
+        };
+
+        // The last move shows through the overlay as a marker
+        let last_move = if is_in_check {
+            Highlight::Check
+        } else if is_capture {
+            Highlight::Capture
+        } else if has_changed {
+            Highlight::LastMove
+        } else {
+            Highlight::None
+        };
+        let overlay = self.overlay_highlight(square).unwrap_or(Highlight::None);
+        let (highlight, mark) = match self.selection {
+            Some(ref selection) if selection.cursor == square => (Highlight::Cursor, overlay),
+            Some(ref selection) if selection.from == Some(square) => (Highlight::Selected, overlay),
+            Some(ref selection) if selection.targets.contains(&square) => {
+                (Highlight::Target, overlay)
+            }
+            _ if last_move == Highlight::None => (overlay, Highlight::None),
+            _ => (last_move, overlay),
+        };
+        // pleco's on_light_square is true for the dark squares, a1 is dark
+        let is_light = (rank + file) % 2 == 1;
+        self.theme
+            .marked_square_str(piece, is_light, highlight, mark)
+    }
+
+    /// Whether the blindfold hides the piece
+    fn hides(&self, piece: Piece) -> bool {
+        if self.peeking || piece.type_of() == PieceType::None {
+            return false;
+        }
+        match self.blindfold {
+            Some(Blindfold::AllPieces) => true,
+            Some(Blindfold::Pieces(player)) => piece.player_lossy() == player,
+            _ => false,
+        }
+    }
+
+    /// The highlight of the overlay on the square, if any
+    fn overlay_highlight(&self, square: SQ) -> Option<Highlight> {
+        let on = |bits: u64| bits & square.to_bb().0 != 0;
+        match self.overlay? {
+            Overlay::Moves(sq) if sq == square => Some(Highlight::Selected),
+            Overlay::Moves(sq) => {
+                if self.destinations(sq).contains(&square) {
+                    Some(Highlight::Target)
+                } else {
+                    None
+                }
+            }
+            Overlay::Attacks(player) if on(self.attacked_by(player)) => Some(Highlight::Attacked),
+            Overlay::Hanging if on(self.hanging()) => Some(Highlight::Hanging),
+            Overlay::Attacks(_) | Overlay::Hanging => None,
+        }
+    }
+
+    /// The legal destinations of the piece on the square, even if its player isn't to move
+    fn destinations(&self, sq: SQ) -> Vec<SQ> {
+        let piece = self.board.piece_at_sq(sq);
+        if piece == Piece::None {
+            return Vec::new();
+        }
+        let board = if piece.player_lossy() == self.board.turn() {
+            self.board.clone()
+        } else {
+            // The same position with the other player to move and without en passant
+            let fen = self.board.fen();
+            let fields: Vec<&str> = fen.split(' ').collect();
+            let turn = if fields[1] == "w" { "b" } else { "w" };
+            let fen = format!(
+                "{} {} {} - {}",
+                fields[0],
+                turn,
+                fields[2],
+                fields[4..].join(" ")
+            );
+            match Board::from_fen(&fen) {
+                Ok(board) => board,
+                // The player to move is in check, so there is no such position
+                Err(_) => return Vec::new(),
+            }
+        };
+        board
+            .generate_moves()
+            .iter()
+            .filter(|mv| mv.get_src() == sq)
+            .map(|mv| mv.get_dest())
+            .collect()
+    }
+
+    /// The squares attacked by the pieces of the player
+    fn attacked_by(&self, player: Player) -> u64 {
+        let occupied = self.board.occupied();
+        let pieces = self.board.get_occupied_player(player).0;
+        (0..64)
+            .map(SQ)
+            .filter(|sq| self.board.attackers_to(*sq, occupied).0 & pieces != 0)
+            .fold(0, |bits, sq| bits | sq.to_bb().0)
+    }
+
+    /// The pieces that the opponent attacks and that their player doesn't defend
+    fn hanging(&self) -> u64 {
+        let occupied = self.board.occupied();
+        (0..64)
+            .map(SQ)
+            .filter(|sq| {
+                let piece = self.board.piece_at_sq(*sq);
+                if piece == Piece::None || piece.type_of() == PieceType::K {
+                    return false;
+                }
+                let player = piece.player_lossy();
+                let attackers = self.board.attackers_to(*sq, occupied).0;
+                attackers & self.board.get_occupied_player(!player).0 != 0
+                    && attackers & self.board.get_occupied_player(player).0 == 0
+            })
+            .fold(0, |bits, sq| bits | sq.to_bb().0)
+    }
+}
+
+/// The width of the terminal, from the terminal itself or from `COLUMNS` as set by most shells
+pub fn terminal_width() -> usize {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0
+        && size.ws_col > 0
+    {
+        return size.ws_col as usize;
+    }
+    env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .filter(|columns| *columns > 0)
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::Candidate;
+    use crate::move_input::parse_square;
+
+    /// The board after the moves, given like `e2e4`
+    fn board_after(fen: &str, moves: &[&str]) -> CliBoard {
+        let mut board = CliBoard::from_fen(fen).unwrap();
+        for mv in moves {
+            assert!(board.apply_uci_move(mv), "illegal move {}", mv);
+        }
+        board
+    }
+
+    const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    /// The first moves of the Ruy Lopez, which make eight pairs of moves
+    const RUY_LOPEZ: [&str; 15] = [
+        "e2e4", "e7e5", "g1f3", "b8c6", "f1b5", "a7a6", "b5a4", "g8f6", "e1g1", "f8e7", "f1e1",
+        "b7b5", "a4b3", "d7d6", "c2c3",
+    ];
+
+    #[test]
+    fn moves_are_paired_by_number() {
+        let board = board_after(START, &["e2e4", "e7e5", "g1f3"]);
+        assert_eq!(board.move_pairs(), vec!["1. e4 e5", "2. Nf3"]);
+
+        // A game may start with a move of black
+        let board = board_after(
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 2 3",
+            &["g8f6", "f3e5"],
+        );
+        assert_eq!(board.move_pairs(), vec!["3... Nf6", "4. Nxe5"]);
+
+        assert!(board_after(START, &[]).move_pairs().is_empty());
+        assert_eq!(board_after(START, &[]).move_list_str(80), "No moves yet.");
+    }
+
+    #[test]
+    fn move_lists_fill_the_width() {
+        let board = board_after(START, &RUY_LOPEZ);
+        // The longest pairs, like `2. Nf3 Nc6`, make the columns 13 characters wide
+        assert_eq!(
+            board.move_list_str(80),
+            "1. e4 e5     3. Bb5 a6    5. O-O Be7   7. Bb3 d6\n\
+             2. Nf3 Nc6   4. Ba4 Nf6   6. Re1 b5    8. c3"
+        );
+        assert_eq!(
+            board.move_list_str(39),
+            "1. e4 e5     4. Ba4 Nf6   7. Bb3 d6\n\
+             2. Nf3 Nc6   5. O-O Be7   8. c3\n\
+             3. Bb5 a6    6. Re1 b5"
+        );
+        assert_eq!(
+            board.move_list_str(38),
+            "1. e4 e5     5. O-O Be7\n\
+             2. Nf3 Nc6   6. Re1 b5\n\
+             3. Bb5 a6    7. Bb3 d6\n\
+             4. Ba4 Nf6   8. c3"
+        );
+        // Narrow terminals get a single column
+        let list = board.move_list_str(10);
+        assert_eq!(list.lines().count(), 8);
+        assert_eq!(list.lines().next(), Some("1. e4 e5"));
+        assert_eq!(list.lines().last(), Some("8. c3"));
+        assert_eq!(board.move_list_str(0), list);
+    }
+
+    /// The squares given like `e4`
+    fn squares(names: &[&str]) -> Vec<SQ> {
+        names
+            .iter()
+            .map(|name| parse_square(name).unwrap())
+            .collect()
+    }
+
+    fn bits(names: &[&str]) -> u64 {
+        squares(names)
+            .iter()
+            .fold(0, |bits, sq| bits | sq.to_bb().0)
+    }
+
+    #[test]
+    fn undefended_attacked_pieces_are_hanging() {
+        // The rooks attack the bishop on b5, the knight on e5 and the pawn on h7,
+        // but the pawn on d4 defends the knight. The bishop attacks the defended rook on e8.
+        let board = board_after("1r1kr3/7p/8/1B2N3/3P4/8/8/4K2R w - - 0 1", &[]);
+        assert_eq!(board.hanging(), bits(&["b5", "h7"]));
+
+        // Without the pawn the knight is hanging too
+        let board = board_after("1r1kr3/7p/8/1B2N3/8/8/8/4K2R w - - 0 1", &[]);
+        assert_eq!(board.hanging(), bits(&["b5", "e5", "h7"]));
+    }
+
+    #[test]
+    fn pinned_pieces_attack_but_dont_let_attacks_through() {
+        // The knight on d2 is pinned by the bishop on b4, yet it still attacks
+        let board = board_after("4k3/8/8/8/1b6/8/3N4/4K3 w - - 0 1", &[]);
+        let knight = bits(&["b1", "b3", "c4", "e4", "f3", "f1"]);
+        assert_eq!(board.attacked_by(Player::White) & knight, knight);
+        // The bishop attacks up to the pinned knight, not beyond it
+        let bishop = board.attacked_by(Player::Black);
+        assert_ne!(bishop & bits(&["d2"]), 0);
+        assert_eq!(bishop & bits(&["e1"]), 0);
+        // The pinned knight can't move
+        assert!(board.destinations(parse_square("d2").unwrap()).is_empty());
+    }
+
+    #[test]
+    fn destinations_are_legal_moves_of_either_player() {
+        let board = board_after(START, &["e2e4"]);
+        let mut knight = board.destinations(parse_square("g1").unwrap());
+        knight.sort_by_key(|sq| sq.0);
+        assert_eq!(knight, squares(&["e2", "f3", "h3"]));
+        // Black is to move, but the pawn of white can still be shown
+        assert_eq!(
+            board.destinations(parse_square("e4").unwrap()),
+            squares(&["e5"])
+        );
+        let mut pawn = board.destinations(parse_square("d7").unwrap());
+        pawn.sort_by_key(|sq| sq.0);
+        assert_eq!(pawn, squares(&["d5", "d6"]));
+        assert!(board.destinations(parse_square("e3").unwrap()).is_empty());
+    }
+
+    #[test]
+    fn the_last_move_shows_through_the_overlay() {
+        let mut board = board_after(START, &["e2e4"]);
+        board.set_theme(Theme::from_name("mono").unwrap());
+        board.set_overlay(Some(Overlay::Attacks(Player::Black)));
+        // e4 isn't attacked, e2 is empty and e7 is attacked by the black pieces
+        let square = |board: &CliBoard, name: &str| {
+            let sq = parse_square(name).unwrap();
+            board.color_square_string(sq.rank_idx_of_sq() + 1, sq.file_idx_of_sq() + 1)
+        };
+        assert_eq!(square(&board, "e4"), "P*");
+        assert_eq!(square(&board, "d6"), ".:");
+        assert_eq!(square(&board, "e7"), "p:");
+        board.set_overlay(Some(Overlay::Attacks(Player::White)));
+        // The square the pawn left is attacked by the king, bishop, queen and knight
+        assert_eq!(square(&board, "e2"), ":*");
+        assert_eq!(square(&board, "e4"), "P*");
+        assert_eq!(square(&board, "f3"), " :");
+        board.set_overlay(None);
+        assert_eq!(square(&board, "e2"), " *");
+    }
+
+    #[test]
+    fn the_analysis_panel_keeps_its_lines() {
+        let board = board_after(START, &[]);
+        let analysis = Analysis {
+            key: MCTree::state_key(&board.board),
+            white_winrate: 0.5634,
+            playouts: 1200,
+            candidates: vec![Candidate {
+                san: String::from("e4"),
+                visits: 700,
+                winrate: 0.5634,
+            }],
+            pv: vec![String::from("e4"), String::from("e5")],
+            proof: None,
+        };
+        assert_eq!(
+            board.analysis_str(Some(&analysis)),
+            "\n  Engine: White 56.3% | 1200 playouts\n    e4           700 (56.3%)\n\n\n  PV: e4 e5"
+        );
+        assert_eq!(
+            board.analysis_str(None),
+            "\n  Engine: waiting\n\n\n\n  PV: "
+        );
+    }
+}
@@ -0,0 +1,428 @@
+use super::pgn::GameResult;
+use std::fmt;
+
+/// The quantile of the normal distribution for a 95% confidence interval
+const CONFIDENCE_95: f64 = 1.959_964;
+
+/// Converts an expected score into an Elo difference
+pub fn elo_from_score(score: f64) -> f64 {
+    -400. * (1. / score - 1.).log10()
+}
+
+/// Converts an Elo difference into an expected score
+pub fn score_from_elo(elo: f64) -> f64 {
+    1. / (1. + 10f64.powf(-elo / 400.))
+}
+
+/// The results of a match, from the perspective of the first player
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MatchScore {
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+}
+
+impl MatchScore {
+    /// Adds the result of a game, worth the given points for the first player
+    pub fn add(&mut self, points: f32) {
+        if points > 0.75 {
+            self.wins += 1;
+        } else if points < 0.25 {
+            self.losses += 1;
+        } else {
+            self.draws += 1;
+        }
+    }
+
+    pub fn games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+
+    /// The average points per game
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.) / self.games() as f64
+    }
+
+    /// The variance of the points of a single game
+    fn variance(&self) -> f64 {
+        let score = self.score();
+        let games = self.games() as f64;
+        (self.wins as f64 * (1. - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2))
+            / games
+    }
+
+    /// The Elo difference and the margin of its 95% confidence interval
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        if self.games() == 0 {
+            return None;
+        }
+        let score = self.score();
+        if score <= 0. || score >= 1. {
+            // Without any wins or losses, the difference is unbounded
+            return Some((elo_from_score(score), f64::INFINITY));
+        }
+        let error = CONFIDENCE_95 * (self.variance() / self.games() as f64).sqrt();
+        let low = elo_from_score((score - error).max(0.));
+        let high = elo_from_score((score + error).min(1.));
+        Some((elo_from_score(score), (high - low) / 2.))
+    }
+
+    /// The log-likelihood ratio of `elo1` against `elo0`.
+    ///
+    /// Uses the normal approximation of the generalized SPRT.
+    pub fn llr(&self, elo0: f64, elo1: f64) -> f64 {
+        let variance = self.variance();
+        if self.games() == 0 || variance == 0. {
+            return 0.;
+        }
+        let score0 = score_from_elo(elo0);
+        let score1 = score_from_elo(elo1);
+        self.games() as f64 * (score1 - score0) * (2. * self.score() - score0 - score1)
+            / (2. * variance)
+    }
+}
+
+impl fmt::Display for MatchScore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "+{} ={} -{}", self.wins, self.draws, self.losses)?;
+        if self.games() > 0 {
+            write!(f, " ({:.1}%)", self.score() * 100.)?;
+        }
+        Ok(())
+    }
+}
+
+/// The decision of a sequential probability ratio test
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SprtVerdict {
+    /// The Elo difference is at most `elo0`
+    AcceptH0,
+    /// The Elo difference is at least `elo1`
+    AcceptH1,
+    /// More games are needed
+    Continue,
+}
+
+/// A sequential probability ratio test of `elo0` against `elo1`
+#[derive(Debug, Clone, Copy)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    /// The probability of a false positive
+    pub alpha: f64,
+    /// The probability of a false negative
+    pub beta: f64,
+}
+
+impl Sprt {
+    /// Parses a test like `0,10` or `0,10,0.05,0.05`
+    pub fn parse(spec: &str) -> Option<Sprt> {
+        let values = spec
+            .split(',')
+            .map(|value| value.parse::<f64>().ok())
+            .collect::<Option<Vec<f64>>>()?;
+        let sprt = match values[..] {
+            [elo0, elo1] => Sprt {
+                elo0,
+                elo1,
+                alpha: 0.05,
+                beta: 0.05,
+            },
+            [elo0, elo1, alpha, beta] => Sprt {
+                elo0,
+                elo1,
+                alpha,
+                beta,
+            },
+            _ => return None,
+        };
+        let valid_probability = |p: f64| p > 0. && p < 1.;
+        if sprt.elo0 < sprt.elo1 && valid_probability(sprt.alpha) && valid_probability(sprt.beta) {
+            Some(sprt)
+        } else {
+            None
+        }
+    }
+
+    /// The lower and upper bound of the log-likelihood ratio
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1. - self.alpha)).ln(),
+            ((1. - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    pub fn verdict(&self, score: &MatchScore) -> SprtVerdict {
+        let llr = score.llr(self.elo0, self.elo1);
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            SprtVerdict::AcceptH1
+        } else if llr <= lower {
+            SprtVerdict::AcceptH0
+        } else {
+            SprtVerdict::Continue
+        }
+    }
+
+    pub fn info_str(&self, score: &MatchScore) -> String {
+        let (lower, upper) = self.bounds();
+        let verdict = match self.verdict(score) {
+            SprtVerdict::AcceptH0 => "H0 accepted",
+            SprtVerdict::AcceptH1 => "H1 accepted",
+            SprtVerdict::Continue => "inconclusive",
+        };
+        format!(
+            "SPRT elo0:{} elo1:{} | llr {:.2} ({:.2}, {:.2}) | {}",
+            self.elo0,
+            self.elo1,
+            score.llr(self.elo0, self.elo1),
+            lower,
+            upper,
+            verdict
+        )
+    }
+}
+
+/// The number of virtual draws between each pair of opponents.
+///
+/// Like the prior of BayesElo, it keeps the ratings of players without wins or losses finite.
+const PRIOR_DRAWS: f64 = 2.;
+/// The advantage of playing white, in Elo, with the default of BayesElo
+pub const WHITE_ADVANTAGE: f64 = 32.8;
+/// The width of the draw margin, in Elo, with the default of BayesElo
+pub const DRAW_ELO: f64 = 97.3;
+/// The maximum number of optimization sweeps over all ratings
+const MAX_ITERATIONS: usize = 1000;
+
+/// Ratings estimated from the games between several players
+pub struct RatingList {
+    /// The Elo of each player, relative to the average
+    pub elo: Vec<f64>,
+    /// The margin of the 95% confidence interval of each rating
+    pub margin: Vec<f64>,
+}
+
+/// The results of the games between two players, in the colors given by the index
+#[derive(Clone, Copy, Default)]
+struct PairResults {
+    white_wins: f64,
+    draws: f64,
+    black_wins: f64,
+}
+
+/// The probabilities of a white win, a draw and a black win for the given
+/// rating difference, in the model of BayesElo
+fn result_probabilities(elo_diff: f64) -> (f64, f64, f64) {
+    let white_wins = score_from_elo(elo_diff + WHITE_ADVANTAGE - DRAW_ELO);
+    let black_wins = score_from_elo(-elo_diff - WHITE_ADVANTAGE - DRAW_ELO);
+    let draws = (1. - white_wins - black_wins).max(1e-12);
+    (white_wins, draws, black_wins)
+}
+
+/// The log-likelihood of the results for the given ratings
+fn log_likelihood(results: &[Vec<PairResults>], elo: &[f64]) -> f64 {
+    let mut likelihood = 0.;
+    for (white, row) in results.iter().enumerate() {
+        for (black, pair) in row.iter().enumerate() {
+            if pair.white_wins + pair.draws + pair.black_wins == 0. {
+                continue;
+            }
+            let (white_wins, draws, black_wins) = result_probabilities(elo[white] - elo[black]);
+            likelihood += pair.white_wins * white_wins.ln()
+                + pair.draws * draws.ln()
+                + pair.black_wins * black_wins.ln();
+        }
+    }
+    likelihood
+}
+
+/// Estimates the ratings with maximum likelihood, like BayesElo.
+///
+/// Each game is given by the index of the white player, the index of the black player
+/// and the result.
+pub fn bayes_elo(players: usize, games: &[(usize, usize, GameResult)]) -> RatingList {
+    let mut results = vec![vec![PairResults::default(); players]; players];
+    let mut opponents = vec![vec![false; players]; players];
+    for &(white, black, result) in games {
+        let pair = &mut results[white][black];
+        match result {
+            GameResult::WhiteWins => pair.white_wins += 1.,
+            GameResult::Draw => pair.draws += 1.,
+            GameResult::BlackWins => pair.black_wins += 1.,
+        }
+        opponents[white][black] = true;
+        opponents[black][white] = true;
+    }
+    // Add the virtual draws, half of them with each color
+    for (first, row) in opponents.iter().enumerate() {
+        for (second, played) in row.iter().enumerate() {
+            if *played {
+                results[first][second].draws += PRIOR_DRAWS / 2.;
+            }
+        }
+    }
+
+    // Maximize the likelihood with Newton steps, one rating at a time
+    let mut elo = vec![0.; players];
+    let derivatives = |elo: &mut Vec<f64>, index: usize| {
+        let h = 0.5;
+        let value = elo[index];
+        let center = log_likelihood(&results, elo);
+        elo[index] = value + h;
+        let above = log_likelihood(&results, elo);
+        elo[index] = value - h;
+        let below = log_likelihood(&results, elo);
+        elo[index] = value;
+        (
+            (above - below) / (2. * h),
+            (above - 2. * center + below) / (h * h),
+        )
+    };
+    for _ in 0..MAX_ITERATIONS {
+        let mut max_step: f64 = 0.;
+        for index in 0..players {
+            let (gradient, curvature) = derivatives(&mut elo, index);
+            let step = if curvature < 0. {
+                (-gradient / curvature).clamp(-100., 100.)
+            } else {
+                10. * gradient.signum()
+            };
+            elo[index] += step;
+            max_step = max_step.max(step.abs());
+        }
+        if max_step < 1e-3 {
+            break;
+        }
+    }
+
+    let margin = (0..players)
+        .map(|index| {
+            let (_, curvature) = derivatives(&mut elo, index);
+            if curvature < 0. {
+                CONFIDENCE_95 / (-curvature).sqrt()
+            } else {
+                f64::INFINITY
+            }
+        })
+        .collect();
+    let average = elo.iter().sum::<f64>() / players as f64;
+    RatingList {
+        elo: elo.iter().map(|elo| elo - average).collect(),
+        margin,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(wins: usize, draws: usize, losses: usize) -> MatchScore {
+        MatchScore {
+            wins,
+            draws,
+            losses,
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() < tolerance,
+            "{} isn't close to {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn elo_and_score_convert_into_each_other() {
+        assert_close(elo_from_score(0.5), 0., 1e-9);
+        assert_close(elo_from_score(0.75), 190.849, 1e-3);
+        assert_close(elo_from_score(0.25), -190.849, 1e-3);
+        assert_close(score_from_elo(400.), 10. / 11., 1e-9);
+        assert_close(score_from_elo(elo_from_score(0.64)), 0.64, 1e-9);
+    }
+
+    #[test]
+    fn elo_has_error_bars() {
+        // A score of 70% with a variance of 0.16 per game
+        let (elo, margin) = score(60, 20, 20).elo().unwrap();
+        assert_close(elo, 147.191, 1e-3);
+        assert_close(margin, 66.013, 1e-3);
+        // Four times the games halve the error of the score
+        let (_, smaller_margin) = score(240, 80, 80).elo().unwrap();
+        assert!(smaller_margin < margin / 1.9);
+
+        assert!(score(0, 0, 0).elo().is_none());
+        let (elo, margin) = score(5, 0, 0).elo().unwrap();
+        assert_eq!(elo, f64::INFINITY);
+        assert_eq!(margin, f64::INFINITY);
+    }
+
+    #[test]
+    fn llr_matches_the_normal_approximation() {
+        assert_close(score(60, 20, 20).llr(0., 10.), 1.7337, 1e-4);
+        assert_close(score(1000, 1000, 960).llr(0., 10.), -0.1124, 1e-4);
+        assert_eq!(score(0, 10, 0).llr(0., 10.), 0.);
+        assert_eq!(score(0, 0, 0).llr(0., 10.), 0.);
+    }
+
+    #[test]
+    fn sprt_decides_at_its_bounds() {
+        let sprt = Sprt::parse("0,10").unwrap();
+        let (lower, upper) = sprt.bounds();
+        assert_close(lower, -2.9444, 1e-4);
+        assert_close(upper, 2.9444, 1e-4);
+
+        assert_eq!(sprt.verdict(&score(600, 200, 200)), SprtVerdict::AcceptH1);
+        assert_eq!(sprt.verdict(&score(1000, 1000, 960)), SprtVerdict::Continue);
+        assert_eq!(sprt.verdict(&score(200, 200, 600)), SprtVerdict::AcceptH0);
+    }
+
+    #[test]
+    fn sprt_rejects_invalid_tests() {
+        let sprt = Sprt::parse("-5,5,0.1,0.2").unwrap();
+        assert_eq!(
+            (sprt.elo0, sprt.elo1, sprt.alpha, sprt.beta),
+            (-5., 5., 0.1, 0.2)
+        );
+        assert!(Sprt::parse("10,0").is_none());
+        assert!(Sprt::parse("0,10,0.05").is_none());
+        assert!(Sprt::parse("0,10,0,0.05").is_none());
+        assert!(Sprt::parse("0,ten").is_none());
+    }
+
+    #[test]
+    fn bayes_elo_finds_the_maximum_likelihood() {
+        use GameResult::*;
+        let mut games = vec![(0, 1, WhiteWins); 3];
+        games.extend(vec![(0, 1, Draw), (1, 0, WhiteWins), (1, 0, Draw)]);
+        games.extend(vec![(1, 0, BlackWins); 2]);
+        let ratings = bayes_elo(2, &games);
+        // Maximized numerically, with the virtual draws and the white advantage
+        assert_close(ratings.elo[0], 68.955, 0.05);
+        assert_close(ratings.elo[1], -68.955, 0.05);
+        assert_close(ratings.margin[0], 195.63, 0.5);
+    }
+
+    #[test]
+    fn bayes_elo_orders_and_centers_the_ratings() {
+        use GameResult::*;
+        // The first player beats the second, who beats the third
+        let mut games = Vec::new();
+        for _ in 0..5 {
+            games.extend(vec![(0, 1, WhiteWins), (1, 0, BlackWins), (1, 0, Draw)]);
+            games.extend(vec![(1, 2, WhiteWins), (2, 1, BlackWins), (2, 1, Draw)]);
+        }
+        let ratings = bayes_elo(3, &games);
+        assert!(ratings.elo[0] > ratings.elo[1] && ratings.elo[1] > ratings.elo[2]);
+        assert_close(ratings.elo.iter().sum::<f64>(), 0., 1e-6);
+        assert!(ratings.margin.iter().all(|margin| margin.is_finite()));
+
+        // Only draws make equal ratings
+        let ratings = bayes_elo(2, &[(0, 1, Draw), (1, 0, Draw)]);
+        assert_close(ratings.elo[0], 0., 1e-6);
+        assert_close(ratings.elo[1], 0., 1e-6);
+    }
+}
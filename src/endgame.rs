@@ -0,0 +1,283 @@
+use super::eval::piece_value;
+use pleco::{Board, PieceType, Player, SQ};
+
+/// The bonus for a won basic endgame, so that converting it beats any other plan
+const KNOWN_WIN: i32 = 1000;
+/// The value of driving the lone king one step closer to the edge or the mating corner
+const PUSH_WEIGHT: i32 = 20;
+/// The value of bringing the attacking king one step closer to the lone king
+const KING_WEIGHT: i32 = 10;
+/// The light squares, a1 is dark
+const LIGHT_SQUARES: u64 = 0x55AA_55AA_55AA_55AA;
+
+/// The mates against a lone king and the endgames that can't be won
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Endgame {
+    /// K-K, no mate is possible
+    Kings,
+    /// KB-K, no mate is possible
+    Bishop,
+    /// KN-K, no mate is possible
+    Knight,
+    /// KQ-K, mate on any edge
+    Queen,
+    /// KR-K, mate on any edge
+    Rook,
+    /// KBB-K with bishops on both colors, mate in any corner
+    Bishops,
+    /// KBN-K, mate in a corner of the bishop's color
+    BishopKnight,
+    /// KNN-K, which can't be forced
+    Knights,
+    /// KBB-K with both bishops on the same color
+    SameColorBishops,
+}
+
+impl Endgame {
+    /// Recognizes the endgame from the material, together with the stronger player.
+    ///
+    /// With two lone kings white counts as the stronger player.
+    pub fn recognize(board: &Board) -> Option<(Endgame, Player)> {
+        let strong = if board.count_pieces_player(Player::Black) == 1 {
+            Player::White
+        } else if board.count_pieces_player(Player::White) == 1 {
+            Player::Black
+        } else {
+            return None;
+        };
+        let count = |piece_type| board.count_piece(strong, piece_type);
+        let material = (
+            count(PieceType::Q),
+            count(PieceType::R),
+            count(PieceType::B),
+            count(PieceType::N),
+            count(PieceType::P),
+        );
+
+        let endgame = match material {
+            (0, 0, 0, 0, 0) => Endgame::Kings,
+            (0, 0, 1, 0, 0) => Endgame::Bishop,
+            (0, 0, 0, 1, 0) => Endgame::Knight,
+            (1, 0, 0, 0, 0) => Endgame::Queen,
+            (0, 1, 0, 0, 0) => Endgame::Rook,
+            (0, 0, 2, 0, 0) => {
+                let bishops = board.piece_bb(strong, PieceType::B).0;
+                if bishops & LIGHT_SQUARES == 0 || bishops & !LIGHT_SQUARES == 0 {
+                    Endgame::SameColorBishops
+                } else {
+                    Endgame::Bishops
+                }
+            }
+            (0, 0, 1, 1, 0) => Endgame::BishopKnight,
+            (0, 0, 0, 2, 0) => Endgame::Knights,
+            _ => return None,
+        };
+        Some((endgame, strong))
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Endgame::Kings => "K-K",
+            Endgame::Bishop => "KB-K",
+            Endgame::Knight => "KN-K",
+            Endgame::Queen => "KQ-K",
+            Endgame::Rook => "KR-K",
+            Endgame::Bishops => "KBB-K",
+            Endgame::BishopKnight => "KBN-K",
+            Endgame::Knights => "KNN-K",
+            Endgame::SameColorBishops => "KBB-K with same colored bishops",
+        }
+    }
+
+    /// Checks if the endgame is a draw with correct play
+    pub fn is_draw(self) -> bool {
+        match self {
+            Endgame::Kings
+            | Endgame::Bishop
+            | Endgame::Knight
+            | Endgame::Knights
+            | Endgame::SameColorBishops => true,
+            Endgame::Queen | Endgame::Rook | Endgame::Bishops | Endgame::BishopKnight => false,
+        }
+    }
+}
+
+/// Checks if the position is a known draw, even though mate is still possible
+pub fn known_draw(board: &Board) -> bool {
+    Endgame::recognize(board).is_some_and(|(endgame, _)| endgame.is_draw())
+}
+
+/// Evaluates a recognized basic endgame in centipawns for white.
+///
+/// Wins are scored by how far the lone king is driven to the edge, or to the right
+/// corner, and how close the kings are, so that a search finds the way to mate.
+pub fn evaluate(board: &Board) -> Option<i32> {
+    let (endgame, strong) = Endgame::recognize(board)?;
+    if endgame.is_draw() {
+        return Some(0);
+    }
+
+    let weak_king = board.king_sq(!strong);
+    let strong_king = board.king_sq(strong);
+    let push = match endgame {
+        Endgame::BishopKnight => {
+            // Only the corners of the bishop's color are mating squares
+            let bishop = board.piece_bb(strong, PieceType::B).0;
+            let corners = if bishop & LIGHT_SQUARES != 0 {
+                [SQ(7), SQ(56)]
+            } else {
+                [SQ(0), SQ(63)]
+            };
+            14 - corners
+                .iter()
+                .map(|corner| manhattan_distance(weak_king, *corner))
+                .min()
+                .unwrap_or(0)
+        }
+        _ => center_distance(weak_king),
+    };
+    let material: i32 = [PieceType::Q, PieceType::R, PieceType::B, PieceType::N]
+        .iter()
+        .map(|piece_type| board.count_piece(strong, *piece_type) as i32 * piece_value(*piece_type))
+        .sum();
+
+    let score = KNOWN_WIN
+        + material
+        + PUSH_WEIGHT * push
+        + KING_WEIGHT * (7 - chebyshev_distance(strong_king, weak_king));
+    Some(match strong {
+        Player::White => score,
+        Player::Black => -score,
+    })
+}
+
+/// The number of steps from the square to the four center squares, from 0 to 6
+fn center_distance(sq: SQ) -> i32 {
+    let file = sq.file_idx_of_sq() as i32;
+    let rank = sq.rank_idx_of_sq() as i32;
+    (3 - file).max(file - 4) + (3 - rank).max(rank - 4)
+}
+
+fn manhattan_distance(a: SQ, b: SQ) -> i32 {
+    (a.file_idx_of_sq() as i32 - b.file_idx_of_sq() as i32).abs()
+        + (a.rank_idx_of_sq() as i32 - b.rank_idx_of_sq() as i32).abs()
+}
+
+/// The number of king moves between the squares
+fn chebyshev_distance(a: SQ, b: SQ) -> i32 {
+    (a.file_idx_of_sq() as i32 - b.file_idx_of_sq() as i32)
+        .abs()
+        .max((a.rank_idx_of_sq() as i32 - b.rank_idx_of_sq() as i32).abs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recognize(fen: &str) -> Option<(Endgame, Player)> {
+        Endgame::recognize(&Board::from_fen(fen).unwrap())
+    }
+
+    #[test]
+    fn endgames_are_recognized_by_their_material() {
+        let endgames = [
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+                Endgame::Kings,
+                Player::White,
+            ),
+            (
+                "4k3/8/8/8/8/8/8/2B1K3 w - - 0 1",
+                Endgame::Bishop,
+                Player::White,
+            ),
+            (
+                "4k3/8/8/8/8/8/8/1N2K3 b - - 0 1",
+                Endgame::Knight,
+                Player::White,
+            ),
+            (
+                "4k3/8/8/8/8/8/8/3QK3 w - - 0 1",
+                Endgame::Queen,
+                Player::White,
+            ),
+            (
+                "r3k3/8/8/8/8/8/8/4K3 w - - 0 1",
+                Endgame::Rook,
+                Player::Black,
+            ),
+            (
+                "2b1kb2/8/8/8/8/8/8/4K3 b - - 0 1",
+                Endgame::Bishops,
+                Player::Black,
+            ),
+            (
+                "4k3/8/8/8/8/8/8/1NB1K3 w - - 0 1",
+                Endgame::BishopKnight,
+                Player::White,
+            ),
+            (
+                "4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1",
+                Endgame::Knights,
+                Player::White,
+            ),
+            (
+                "4k3/8/8/8/8/4B3/8/2B1K3 w - - 0 1",
+                Endgame::SameColorBishops,
+                Player::White,
+            ),
+        ];
+        for (fen, endgame, strong) in endgames.iter() {
+            assert_eq!(recognize(fen), Some((*endgame, *strong)), "{}", fen);
+        }
+
+        // Pawns and material on both sides aren't basic endgames
+        assert_eq!(recognize("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"), None);
+        assert_eq!(recognize("4k3/8/8/8/8/8/8/3QK2r w - - 0 1"), None);
+    }
+
+    #[test]
+    fn endgames_without_enough_material_are_draws() {
+        let draws = [
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/2B1K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/1N2K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1",
+            "4k3/8/8/8/8/4B3/8/2B1K3 w - - 0 1",
+        ];
+        for fen in draws.iter() {
+            let board = Board::from_fen(fen).unwrap();
+            assert!(known_draw(&board), "{}", fen);
+            assert_eq!(evaluate(&board), Some(0), "{}", fen);
+        }
+
+        let wins = [
+            "4k3/8/8/8/8/8/8/3QK3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/1NB1K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1",
+        ];
+        for fen in wins.iter() {
+            assert!(!known_draw(&Board::from_fen(fen).unwrap()), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn bishop_and_knight_mate_in_the_corner_of_the_bishop() {
+        let score = |fen| evaluate(&Board::from_fen(fen).unwrap()).unwrap();
+
+        // A dark squared bishop mates on a1 or h8
+        assert!(
+            score("7k/8/8/8/8/8/8/2B1KN2 w - - 0 1") > score("k7/8/8/8/8/8/8/2B1KN2 w - - 0 1")
+        );
+        assert!(
+            score("8/8/8/8/8/8/8/k1B1KN2 w - - 0 1") > score("8/8/8/8/8/8/8/2B1KN1k w - - 0 1")
+        );
+        // A light squared bishop mates on h1 or a8
+        assert!(score("k7/8/8/8/8/8/8/3BKN2 w - - 0 1") > score("7k/8/8/8/8/8/8/3BKN2 w - - 0 1"));
+        assert!(score("8/8/8/8/8/8/8/3BKN1k w - - 0 1") > score("8/8/8/8/8/8/8/k2BKN2 w - - 0 1"));
+        // The same holds for black, whose scores are negative
+        assert!(
+            score("2b1kn2/8/8/8/8/8/8/7K b - - 0 1") < score("2b1kn2/8/8/8/8/8/8/K7 b - - 0 1")
+        );
+    }
+}
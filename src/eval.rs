@@ -0,0 +1,559 @@
+use super::endgame;
+use pleco::{Board, Piece, PieceType, Player, SQ};
+
+use std::fmt;
+
+/// Piece-square table for pawns, from a8 to h1 as seen by white
+#[rustfmt::skip]
+const PAWN_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+/// Piece-square table for knights, from a8 to h1 as seen by white
+#[rustfmt::skip]
+const KNIGHT_TABLE: [i32; 64] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+];
+
+/// Piece-square table for bishops, from a8 to h1 as seen by white
+#[rustfmt::skip]
+const BISHOP_TABLE: [i32; 64] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+
+/// Piece-square table for rooks, from a8 to h1 as seen by white
+#[rustfmt::skip]
+const ROOK_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+
+/// Piece-square table for queens, from a8 to h1 as seen by white
+#[rustfmt::skip]
+const QUEEN_TABLE: [i32; 64] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+     -5,  0,  5,  5,  5,  5,  0, -5,
+      0,  0,  5,  5,  5,  5,  0, -5,
+    -10,  5,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5,  0,  0,  0,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20,
+];
+
+/// Piece-square table for kings, from a8 to h1 as seen by white
+#[rustfmt::skip]
+const KING_TABLE: [i32; 64] = [
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -20,-30,-30,-40,-40,-30,-30,-20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+     20, 20,  0,  0,  0,  0, 20, 20,
+     20, 30, 10,  0,  0, 10, 30, 20,
+];
+
+/// The material value of a piece type, in centipawns
+pub fn piece_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::P => 100,
+        PieceType::N => 320,
+        PieceType::B => 330,
+        PieceType::R => 500,
+        PieceType::Q => 900,
+        _ => 0,
+    }
+}
+
+/// The index of the square in the piece-square tables
+fn table_index(piece: Piece, sq: SQ) -> usize {
+    // The tables start at a8, which is the last square for white
+    let index = match piece.player_lossy() {
+        Player::White => sq.flip().0,
+        Player::Black => sq.0,
+    };
+    index as usize
+}
+
+/// The positional value of a piece on the given square in the middlegame,
+/// for the owner of the piece
+pub fn psq_value(piece: Piece, sq: SQ) -> i32 {
+    let table = match piece.type_of() {
+        PieceType::P => &PAWN_TABLE,
+        PieceType::N => &KNIGHT_TABLE,
+        PieceType::B => &BISHOP_TABLE,
+        PieceType::R => &ROOK_TABLE,
+        PieceType::Q => &QUEEN_TABLE,
+        PieceType::K => &KING_TABLE,
+        _ => return 0,
+    };
+    table[table_index(piece, sq)]
+}
+
+/// The positional value of a piece on the given square in the endgame,
+/// for the owner of the piece
+pub fn psq_end_value(piece: Piece, sq: SQ) -> i32 {
+    match piece.type_of() {
+        PieceType::P => PAWN_END_TABLE[table_index(piece, sq)],
+        PieceType::K => KING_END_TABLE[table_index(piece, sq)],
+        // The other pieces are placed the same way in the endgame
+        _ => psq_value(piece, sq),
+    }
+}
+
+/// Endgame piece-square table for pawns, from a8 to h1 as seen by white
+#[rustfmt::skip]
+const PAWN_END_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    80, 80, 80, 80, 80, 80, 80, 80,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    30, 30, 30, 30, 30, 30, 30, 30,
+    20, 20, 20, 20, 20, 20, 20, 20,
+    10, 10, 10, 10, 10, 10, 10, 10,
+     0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+/// Endgame piece-square table for kings, from a8 to h1 as seen by white
+#[rustfmt::skip]
+const KING_END_TABLE: [i32; 64] = [
+    -50,-40,-30,-20,-20,-30,-40,-50,
+    -30,-20,-10,  0,  0,-10,-20,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-30,  0,  0,  0,  0,-30,-30,
+    -50,-30,-30,-30,-30,-30,-30,-50,
+];
+
+/// The game phase of the starting position
+const MAX_PHASE: i32 = 24;
+
+/// Bonus for a passed pawn in the middlegame, by the rank from its owner's side
+const PASSED_MG: [i32; 8] = [0, 5, 10, 15, 25, 40, 60, 0];
+/// Bonus for a passed pawn in the endgame, by the rank from its owner's side
+const PASSED_EG: [i32; 8] = [0, 10, 20, 30, 50, 75, 110, 0];
+
+/// All squares of the a-file
+const FILE_A: u64 = 0x0101_0101_0101_0101;
+
+const PIECE_TYPES: [PieceType; 6] = [
+    PieceType::P,
+    PieceType::N,
+    PieceType::B,
+    PieceType::R,
+    PieceType::Q,
+    PieceType::K,
+];
+
+/// A term of the evaluation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Term {
+    Material,
+    PieceSquares,
+    Mobility,
+    PawnStructure,
+    KingSafety,
+}
+
+impl Term {
+    /// All terms, in the order of the trace
+    pub const ALL: [Term; 5] = [
+        Term::Material,
+        Term::PieceSquares,
+        Term::Mobility,
+        Term::PawnStructure,
+        Term::KingSafety,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Term::Material => "Material",
+            Term::PieceSquares => "Piece-squares",
+            Term::Mobility => "Mobility",
+            Term::PawnStructure => "Pawn structure",
+            Term::KingSafety => "King safety",
+        }
+    }
+}
+
+/// A value in the middlegame and the endgame
+#[derive(Debug, Clone, Copy, Default)]
+struct Tapered {
+    mg: i32,
+    eg: i32,
+}
+
+impl Tapered {
+    fn new(mg: i32, eg: i32) -> Tapered {
+        Tapered { mg, eg }
+    }
+
+    fn add(&mut self, mg: i32, eg: i32) {
+        self.mg += mg;
+        self.eg += eg;
+    }
+
+    /// Interpolates between the middlegame and the endgame
+    fn taper(self, phase: i32) -> i32 {
+        (self.mg * phase + self.eg * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+}
+
+/// The breakdown of an evaluation into its terms
+pub struct EvalTrace {
+    /// The game phase, from 24 in the opening to 0 in the endgame
+    pub phase: i32,
+    /// The value of each term for white and black, in centipawns
+    pub terms: [(i32, i32); 5],
+}
+
+impl EvalTrace {
+    /// The value of the given term for white and black
+    pub fn term(&self, term: Term) -> (i32, i32) {
+        self.terms[term as usize]
+    }
+
+    /// The total evaluation, from the perspective of white
+    pub fn total(&self) -> i32 {
+        self.terms.iter().map(|(white, black)| white - black).sum()
+    }
+}
+
+impl fmt::Display for EvalTrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<16}{:>8}{:>8}{:>8}",
+            "Term", "White", "Black", "Total"
+        )?;
+        for &term in &Term::ALL {
+            let (white, black) = self.term(term);
+            writeln!(
+                f,
+                "{:<16}{:>8}{:>8}{:>+8}",
+                term.name(),
+                white,
+                black,
+                white - black
+            )?;
+        }
+        writeln!(
+            f,
+            "{:<16}{:>8}",
+            "Phase",
+            format!("{}/{}", self.phase, MAX_PHASE)
+        )?;
+        write!(
+            f,
+            "{:<16}{:>24}",
+            "Evaluation",
+            format!("{:+}", self.total())
+        )
+    }
+}
+
+/// Evaluates the board, in centipawns.
+///
+/// Positive values are good for white, negative values are good for black.
+pub fn evaluate(board: &Board) -> i32 {
+    // Basic endgames have their own evaluation
+    endgame::evaluate(board).unwrap_or_else(|| trace(board).total())
+}
+
+/// Evaluates the board and keeps the value of each term
+pub fn trace(board: &Board) -> EvalTrace {
+    let phase = game_phase(board);
+    let mut terms = [(0, 0); 5];
+    for &term in &Term::ALL {
+        let white = term_value(board, term, Player::White).taper(phase);
+        let black = term_value(board, term, Player::Black).taper(phase);
+        terms[term as usize] = (white, black);
+    }
+    EvalTrace { phase, terms }
+}
+
+/// Determines how far the game has progressed by the remaining pieces
+fn game_phase(board: &Board) -> i32 {
+    let phase = board.count_piece(Player::White, PieceType::N) as i32
+        + board.count_piece(Player::Black, PieceType::N) as i32
+        + board.count_piece(Player::White, PieceType::B) as i32
+        + board.count_piece(Player::Black, PieceType::B) as i32
+        + 2 * (board.count_piece(Player::White, PieceType::R) as i32
+            + board.count_piece(Player::Black, PieceType::R) as i32)
+        + 4 * (board.count_piece(Player::White, PieceType::Q) as i32
+            + board.count_piece(Player::Black, PieceType::Q) as i32);
+    if phase > MAX_PHASE {
+        MAX_PHASE
+    } else {
+        phase
+    }
+}
+
+/// Determines the value of a term for one player
+fn term_value(board: &Board, term: Term, player: Player) -> Tapered {
+    match term {
+        Term::Material => material(board, player),
+        Term::PieceSquares => piece_squares(board, player),
+        Term::Mobility => mobility(board, player),
+        Term::PawnStructure => pawn_structure(board, player),
+        Term::KingSafety => king_safety(board, player),
+    }
+}
+
+fn material(board: &Board, player: Player) -> Tapered {
+    let value = PIECE_TYPES
+        .iter()
+        .map(|&piece_type| board.count_piece(player, piece_type) as i32 * piece_value(piece_type))
+        .sum();
+    Tapered::new(value, value)
+}
+
+fn piece_squares(board: &Board, player: Player) -> Tapered {
+    let mut value = Tapered::default();
+    for &piece_type in &PIECE_TYPES {
+        let piece = Piece::make_lossy(player, piece_type);
+        for sq in board.piece_bb(player, piece_type) {
+            value.add(psq_value(piece, sq), psq_end_value(piece, sq));
+        }
+    }
+    value
+}
+
+fn mobility(board: &Board, player: Player) -> Tapered {
+    let own = board.get_occupied_player(player).0;
+    let mut value = Tapered::default();
+    for &(piece_type, mg, eg) in &[
+        (PieceType::N, 4, 4),
+        (PieceType::B, 5, 5),
+        (PieceType::R, 2, 4),
+        (PieceType::Q, 1, 2),
+    ] {
+        for sq in board.piece_bb(player, piece_type) {
+            // Squares the piece can reach, which aren't occupied by its own pieces
+            let squares = (board.attacks_from(piece_type, sq, player).0 & !own).count_ones() as i32;
+            value.add(mg * squares, eg * squares);
+        }
+    }
+    value
+}
+
+fn pawn_structure(board: &Board, player: Player) -> Tapered {
+    let pawns = board.piece_bb(player, PieceType::P).0;
+    let mut value = Tapered::default();
+    for sq in board.piece_bb(player, PieceType::P) {
+        let file = sq.0 % 8;
+        let file_mask = FILE_A << file;
+        let adjacent_mask = (if file > 0 { FILE_A << (file - 1) } else { 0 })
+            | (if file < 7 { FILE_A << (file + 1) } else { 0 });
+
+        // Doubled: Another pawn on the same file
+        if (pawns & file_mask).count_ones() > 1 {
+            value.add(-10, -20);
+        }
+        // Isolated: No pawns on the adjacent files
+        if pawns & adjacent_mask == 0 {
+            value.add(-10, -15);
+        }
+        // Passed: No opposing pawns can stop it
+        if board.pawn_passed(player, sq) {
+            let rank = relative_rank(sq, player) as usize;
+            value.add(PASSED_MG[rank], PASSED_EG[rank]);
+        }
+    }
+    value
+}
+
+fn king_safety(board: &Board, player: Player) -> Tapered {
+    let king_sq = board.king_sq(player);
+    let pawns = board.piece_bb(player, PieceType::P).0;
+    let king_file = (king_sq.0 % 8) as i32;
+    let king_rank = relative_rank(king_sq, player) as i32;
+    let mut mg = 0;
+
+    // Pawn shield: Own pawns directly in front of the king
+    for file in (king_file - 1)..=(king_file + 1) {
+        if !(0..8).contains(&file) {
+            continue;
+        }
+        for rank in (king_rank + 1)..=(king_rank + 2) {
+            if (0..8).contains(&rank) {
+                let sq = absolute_sq(file as u8, rank as u8, player);
+                if pawns & (1 << sq.0) != 0 {
+                    mg += 10;
+                }
+            }
+        }
+    }
+
+    // Open file: No own pawn on the file of the king
+    if pawns & (FILE_A << king_file) == 0 {
+        mg -= 20;
+    }
+
+    // Attacks: Opposing pieces attacking the squares around the king
+    let king_zone = board.attacks_from(PieceType::K, king_sq, player).0 | (1 << king_sq.0);
+    let opponent = !player;
+    for &piece_type in &[PieceType::N, PieceType::B, PieceType::R, PieceType::Q] {
+        for sq in board.piece_bb(opponent, piece_type) {
+            let attacks = board.attacks_from(piece_type, sq, opponent).0 & king_zone;
+            mg -= 8 * attacks.count_ones() as i32;
+        }
+    }
+
+    // The king comes out in the endgame, so safety matters less
+    Tapered::new(mg, 0)
+}
+
+/// The rank of the square, counted from the side of the player
+fn relative_rank(sq: SQ, player: Player) -> u8 {
+    match player {
+        Player::White => sq.0 / 8,
+        Player::Black => 7 - sq.0 / 8,
+    }
+}
+
+/// The square on the file and rank, counted from the side of the player
+fn absolute_sq(file: u8, rank: u8, player: Player) -> SQ {
+    match player {
+        Player::White => SQ(rank * 8 + file),
+        Player::Black => SQ((7 - rank) * 8 + file),
+    }
+}
+
+/// Converts an evaluation in centipawns into the probability to win
+pub fn win_probability(score: i32) -> f32 {
+    // A logistic curve, where 400 centipawns give odds of 10:1
+    1. / (1. + 10f32.powf(-score as f32 / 400.))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POSITIONS: [&str; 5] = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3",
+    ];
+
+    /// Swaps the colors of the position and mirrors it vertically
+    fn mirror_fen(fen: &str) -> String {
+        let fields: Vec<&str> = fen.split(' ').collect();
+        let swap_case = |s: &str| -> String {
+            s.chars()
+                .map(|c| {
+                    if c.is_ascii_uppercase() {
+                        c.to_ascii_lowercase()
+                    } else {
+                        c.to_ascii_uppercase()
+                    }
+                })
+                .collect()
+        };
+        let placement: Vec<String> = fields[0].split('/').rev().map(swap_case).collect();
+        let side = if fields[1] == "w" { "b" } else { "w" };
+        let en_passant: String = fields[3]
+            .chars()
+            .map(|c| match c {
+                '3' => '6',
+                '6' => '3',
+                c => c,
+            })
+            .collect();
+        format!(
+            "{} {} {} {} {} {}",
+            placement.join("/"),
+            side,
+            swap_case(fields[2]),
+            en_passant,
+            fields[4],
+            fields[5]
+        )
+    }
+
+    #[test]
+    fn trace_terms_sum_to_the_evaluation() {
+        for fen in &POSITIONS {
+            let board = Board::from_fen(fen).unwrap();
+            let trace = trace(&board);
+            let sum: i32 = Term::ALL
+                .iter()
+                .map(|&term| {
+                    let (white, black) = trace.term(term);
+                    white - black
+                })
+                .sum();
+            assert_eq!(sum, trace.total(), "{}", fen);
+            assert_eq!(trace.total(), evaluate(&board), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn mirrored_positions_negate_the_evaluation() {
+        for fen in &POSITIONS {
+            let board = Board::from_fen(fen).unwrap();
+            let mirrored = Board::from_fen(&mirror_fen(fen)).unwrap();
+            assert_eq!(evaluate(&board), -evaluate(&mirrored), "{}", fen);
+
+            let (trace, mirrored_trace) = (trace(&board), trace(&mirrored));
+            assert_eq!(trace.phase, mirrored_trace.phase);
+            for &term in &Term::ALL {
+                let (white, black) = trace.term(term);
+                assert_eq!(
+                    (black, white),
+                    mirrored_trace.term(term),
+                    "{} {}",
+                    fen,
+                    term.name()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn the_start_position_is_balanced() {
+        let board = Board::start_pos();
+        assert_eq!(evaluate(&board), 0);
+        assert_eq!(trace(&board).phase, MAX_PHASE);
+    }
+
+    #[test]
+    fn win_probability_is_a_logistic_curve() {
+        assert_eq!(win_probability(0), 0.5);
+        assert!((win_probability(400) - 10. / 11.).abs() < 1e-6);
+        assert!((win_probability(150) + win_probability(-150) - 1.).abs() < 1e-6);
+        assert!(win_probability(100) < win_probability(200));
+    }
+}
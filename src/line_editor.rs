@@ -0,0 +1,388 @@
+use super::cli_board::terminal_width;
+use super::tui::{read_keys, Key, RawMode, TICK};
+
+use std::io::{self, Write};
+
+/// The maximum number of remembered lines
+const MAX_HISTORY: usize = 100;
+
+/// Reads lines from the terminal with editing, a history and tab completion.
+///
+/// Falls back to plain lines when the input or output isn't a terminal.
+pub struct LineEditor {
+    history: Vec<String>,
+}
+
+/// The line being edited
+struct Line {
+    chars: Vec<char>,
+    /// The position of the cursor, from 0 to the length of the line
+    cursor: usize,
+    /// The completions shown after the line until the next key
+    hint: String,
+    /// The line being browsed in the history, which is the new line at its end
+    browsing: usize,
+    /// The new line, kept while browsing the history
+    draft: String,
+}
+
+/// What a key press did to the line
+#[derive(Debug, PartialEq)]
+enum Edit {
+    /// The line is still being edited
+    Changed,
+    /// The line was entered
+    Entered(String),
+    /// The input ended
+    Ended,
+}
+
+impl Line {
+    /// An empty line after the given number of history lines
+    fn new(history_len: usize) -> Line {
+        Line {
+            chars: Vec::new(),
+            cursor: 0,
+            hint: String::new(),
+            browsing: history_len,
+            draft: String::new(),
+        }
+    }
+
+    fn set(&mut self, text: &str) {
+        self.chars = text.chars().collect();
+        self.cursor = self.chars.len();
+    }
+
+    fn text(&self) -> String {
+        self.chars.iter().collect()
+    }
+
+    /// Redraws the prompt and the line, which has to fit on one row
+    fn draw(&self, prompt: &str) {
+        let mut s = format!("\r{}{}", prompt, self.text());
+        let mut back = self.chars.len() - self.cursor;
+        if !self.hint.is_empty() {
+            let room = terminal_width().saturating_sub(prompt.len() + self.chars.len() + 3);
+            let hint: String = self.hint.chars().take(room).collect();
+            back += hint.chars().count() + 2;
+            s.push_str(&format!("  \x1b[2m{}\x1b[0m", hint));
+        }
+        s.push_str("\x1b[K");
+        if back > 0 {
+            s.push_str(&format!("\x1b[{}D", back));
+        }
+        print!("{}", s);
+        let _ = io::stdout().flush();
+    }
+
+    /// Removes the word before the cursor, like Ctrl-W in a shell
+    fn delete_word(&mut self) {
+        let mut start = self.cursor;
+        while start > 0 && self.chars[start - 1] == ' ' {
+            start -= 1;
+        }
+        while start > 0 && self.chars[start - 1] != ' ' {
+            start -= 1;
+        }
+        self.chars.drain(start..self.cursor);
+        self.cursor = start;
+    }
+
+    /// Completes the text before the cursor with the words that start with it.
+    ///
+    /// Several words are completed as far as they agree and shown as a hint.
+    fn complete(&mut self, words: &[String]) {
+        let prefix: String = self.chars[..self.cursor].iter().collect();
+        let matches: Vec<&String> = words
+            .iter()
+            .filter(|word| word.starts_with(&prefix))
+            .collect();
+        let common = match matches.first() {
+            Some(first) => matches.iter().fold(first.as_str(), |common, word| {
+                let len = common
+                    .char_indices()
+                    .zip(word.chars())
+                    .take_while(|((_, a), b)| a == b)
+                    .last()
+                    .map_or(0, |((i, a), _)| i + a.len_utf8());
+                &common[..len]
+            }),
+            None => {
+                // Ring the bell
+                print!("\x07");
+                return;
+            }
+        };
+
+        let rest: Vec<char> = self.chars[self.cursor..].to_vec();
+        self.set(common);
+        if matches.len() == 1 && rest.is_empty() && common.starts_with('/') {
+            // Commands may take an argument
+            self.chars.push(' ');
+            self.cursor += 1;
+        }
+        self.chars.extend(rest);
+        if matches.len() > 1 && common == prefix {
+            let shown: Vec<&str> = matches.iter().map(|word| word.as_str()).collect();
+            self.hint = shown.join(" ");
+        }
+    }
+}
+
+impl LineEditor {
+    pub fn new() -> LineEditor {
+        LineEditor {
+            history: Vec::new(),
+        }
+    }
+
+    /// Reads a line after the prompt, completing it with the given words on tab.
+    ///
+    /// Returns None when the input ends, like after Ctrl-D on an empty line.
+    pub fn read_line(&mut self, prompt: &str, words: &[String]) -> Option<String> {
+        let _raw = match RawMode::enable() {
+            Ok(raw) => raw,
+            Err(_) => return read_plain_line(),
+        };
+
+        let mut line = Line::new(self.history.len());
+        line.draw(prompt);
+
+        loop {
+            for key in read_keys(TICK) {
+                match self.edit(&mut line, key, words) {
+                    Edit::Changed => line.draw(prompt),
+                    Edit::Entered(text) => {
+                        line.draw(prompt);
+                        println!();
+                        return Some(text);
+                    }
+                    Edit::Ended => {
+                        println!();
+                        return None;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Applies a key press to the line, entered lines are added to the history
+    fn edit(&mut self, line: &mut Line, key: Key, words: &[String]) -> Edit {
+        line.hint.clear();
+        match key {
+            Key::Char(c) => {
+                line.chars.insert(line.cursor, c);
+                line.cursor += 1;
+            }
+            Key::Enter => {
+                let text = line.text();
+                if !text.trim().is_empty() && self.history.last() != Some(&text) {
+                    self.history.push(text.clone());
+                    if self.history.len() > MAX_HISTORY {
+                        self.history.remove(0);
+                    }
+                }
+                return Edit::Entered(text);
+            }
+            Key::Eof => return Edit::Ended,
+            Key::Ctrl('d') if line.chars.is_empty() => return Edit::Ended,
+            Key::Delete | Key::Ctrl('d') if line.cursor < line.chars.len() => {
+                line.chars.remove(line.cursor);
+            }
+            Key::Backspace if line.cursor > 0 => {
+                line.cursor -= 1;
+                line.chars.remove(line.cursor);
+            }
+            Key::Left | Key::Ctrl('b') => line.cursor = line.cursor.saturating_sub(1),
+            Key::Right | Key::Ctrl('f') => line.cursor = (line.cursor + 1).min(line.chars.len()),
+            Key::Home | Key::Ctrl('a') => line.cursor = 0,
+            Key::End | Key::Ctrl('e') => line.cursor = line.chars.len(),
+            Key::Ctrl('u') => {
+                line.chars.drain(..line.cursor);
+                line.cursor = 0;
+            }
+            Key::Ctrl('k') => line.chars.truncate(line.cursor),
+            Key::Ctrl('w') => line.delete_word(),
+            Key::Up | Key::Ctrl('p') if line.browsing > 0 => {
+                if line.browsing == self.history.len() {
+                    line.draft = line.text();
+                }
+                line.browsing -= 1;
+                line.set(&self.history[line.browsing]);
+            }
+            Key::Down | Key::Ctrl('n') if line.browsing < self.history.len() => {
+                line.browsing += 1;
+                match self.history.get(line.browsing) {
+                    Some(text) => line.set(text),
+                    None => {
+                        let draft = line.draft.clone();
+                        line.set(&draft);
+                    }
+                }
+            }
+            Key::Tab => line.complete(words),
+            _ => (),
+        }
+        Edit::Changed
+    }
+}
+
+/// Reads a line without editing, None when the input ends
+fn read_plain_line() -> Option<String> {
+    let mut line = String::new();
+    match io::stdin().read_line(&mut line) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(line.trim_end_matches(['\r', '\n']).to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(text: &str, cursor: usize) -> Line {
+        let mut line = Line::new(0);
+        line.set(text);
+        line.cursor = cursor;
+        line
+    }
+
+    fn words(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    /// Types the keys into a new line and returns what the last key did
+    fn type_keys(editor: &mut LineEditor, keys: &[Key]) -> (Line, Edit) {
+        let mut line = Line::new(editor.history.len());
+        let mut edit = Edit::Changed;
+        for key in keys {
+            edit = editor.edit(&mut line, *key, &[]);
+        }
+        (line, edit)
+    }
+
+    fn enter(editor: &mut LineEditor, text: &str) {
+        let mut keys: Vec<Key> = text.chars().map(Key::Char).collect();
+        keys.push(Key::Enter);
+        assert_eq!(type_keys(editor, &keys).1, Edit::Entered(text.to_string()));
+    }
+
+    #[test]
+    fn words_before_the_cursor_are_deleted() {
+        let mut deleted = line("/show e2", 8);
+        deleted.delete_word();
+        assert_eq!(
+            (deleted.text(), deleted.cursor),
+            (String::from("/show "), 6)
+        );
+        // Spaces before the cursor go with the word
+        deleted.delete_word();
+        assert_eq!((deleted.text(), deleted.cursor), (String::new(), 0));
+
+        // The text after the cursor stays
+        let mut deleted = line("e2e4 Nf3 d4", 8);
+        deleted.delete_word();
+        assert_eq!(
+            (deleted.text(), deleted.cursor),
+            (String::from("e2e4  d4"), 5)
+        );
+        let mut deleted = line("e2e4", 0);
+        deleted.delete_word();
+        assert_eq!(deleted.text(), "e2e4");
+    }
+
+    #[test]
+    fn words_are_completed_as_far_as_they_agree() {
+        let moves = words(&["Nc3", "Nf3", "Nh3", "e2e3", "e2e4", "/flip", "/moves"]);
+
+        let mut completed = line("e2", 2);
+        completed.complete(&moves);
+        assert_eq!(completed.text(), "e2e");
+        assert!(completed.hint.is_empty());
+        // Nothing is left to agree on, so the choices are shown
+        completed.complete(&moves);
+        assert_eq!(completed.text(), "e2e");
+        assert_eq!(completed.hint, "e2e3 e2e4");
+
+        let mut completed = line("Nf", 2);
+        completed.complete(&moves);
+        assert_eq!(
+            (completed.text(), completed.cursor),
+            (String::from("Nf3"), 3)
+        );
+
+        // Commands get a space for their argument
+        let mut completed = line("/f", 2);
+        completed.complete(&moves);
+        assert_eq!(completed.text(), "/flip ");
+
+        // Only the text before the cursor is completed
+        let mut completed = line("Nh e", 2);
+        completed.complete(&moves);
+        assert_eq!(
+            (completed.text(), completed.cursor),
+            (String::from("Nh3 e"), 3)
+        );
+
+        let mut completed = line("Qd", 2);
+        completed.complete(&moves);
+        assert_eq!(completed.text(), "Qd");
+    }
+
+    #[test]
+    fn history_is_browsed_and_keeps_the_new_line() {
+        let mut editor = LineEditor::new();
+        enter(&mut editor, "e2e4");
+        enter(&mut editor, "/moves");
+        // Blank lines and repeated lines aren't remembered
+        enter(&mut editor, " ");
+        enter(&mut editor, "/moves");
+        assert_eq!(editor.history, words(&["e2e4", "/moves"]));
+
+        let mut line = Line::new(editor.history.len());
+        for key in [Key::Char('N'), Key::Up].iter() {
+            editor.edit(&mut line, *key, &[]);
+        }
+        assert_eq!(line.text(), "/moves");
+        editor.edit(&mut line, Key::Ctrl('p'), &[]);
+        assert_eq!(line.text(), "e2e4");
+        // The oldest line stays
+        editor.edit(&mut line, Key::Up, &[]);
+        assert_eq!(line.text(), "e2e4");
+        editor.edit(&mut line, Key::Down, &[]);
+        assert_eq!(line.text(), "/moves");
+        editor.edit(&mut line, Key::Ctrl('n'), &[]);
+        assert_eq!((line.text(), line.cursor), (String::from("N"), 1));
+        editor.edit(&mut line, Key::Down, &[]);
+        assert_eq!(line.text(), "N");
+
+        // A line from the history can be edited and entered
+        let (_, edit) = type_keys(&mut editor, &[Key::Up, Key::Backspace, Key::Enter]);
+        assert_eq!(edit, Edit::Entered(String::from("/move")));
+        assert_eq!(editor.history.len(), 3);
+    }
+
+    #[test]
+    fn history_is_limited() {
+        let mut editor = LineEditor::new();
+        for i in 0..MAX_HISTORY + 5 {
+            enter(&mut editor, &i.to_string());
+        }
+        assert_eq!(editor.history.len(), MAX_HISTORY);
+        assert_eq!(editor.history[0], "5");
+    }
+
+    #[test]
+    fn the_input_ends_on_an_empty_line() {
+        let mut editor = LineEditor::new();
+        assert_eq!(type_keys(&mut editor, &[Key::Eof]).1, Edit::Ended);
+        assert_eq!(type_keys(&mut editor, &[Key::Ctrl('d')]).1, Edit::Ended);
+        // Otherwise Ctrl-D deletes the character under the cursor
+        let (line, edit) = type_keys(
+            &mut editor,
+            &[Key::Char('e'), Key::Char('4'), Key::Home, Key::Ctrl('d')],
+        );
+        assert_eq!((line.text(), edit), (String::from("4"), Edit::Changed));
+    }
+}
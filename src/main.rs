@@ -1,31 +1,173 @@
+mod alpha_beta;
+mod analysis;
+mod book_builder;
 mod chess_player;
 mod cli_board;
+mod elo;
+mod endgame;
+mod eval;
+mod line_editor;
+mod match_runner;
 mod mcts;
+mod move_input;
+mod pgn;
+mod player_spec;
+mod playout;
+mod polyglot;
+mod priors;
+mod san;
+mod selection;
+mod theme;
+mod tournament;
+mod tui;
+mod zobrist;
 
-use chess_player::{ChessPlayer, HumanPlayer, RandomPlayer, StoneFish};
-use cli_board::{BoardState, CliBoard};
+use cli_board::{Blindfold, BoardState, CliBoard};
+use player_spec::{create_player, option_value};
 use pleco::*;
 use std::env;
-use std::sync::{
-    mpsc::{self, TryRecvError},
-    Arc, Mutex,
-};
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
 
 use std::time::{Duration, SystemTime};
 
+/// Prints the evaluation of the given position, term by term
+fn print_eval(fen: &str) {
+    let board = if fen.is_empty() {
+        Board::start_pos()
+    } else {
+        match Board::from_fen(fen) {
+            Ok(board) => board,
+            Err(_) => {
+                println!("Invalid fen string!");
+                return;
+            }
+        }
+    };
+    CliBoard::new(board.clone()).color_print();
+    println!("{}", eval::trace(&board));
+    if let Some((endgame, strong)) = endgame::Endgame::recognize(&board) {
+        println!(
+            "Recognized {} for {:?}: {}",
+            endgame.name(),
+            strong,
+            eval::evaluate(&board)
+        );
+    }
+}
+
 fn main() {
     env::set_var("RUST_BACKTRACE", "1");
 
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map(String::as_str) == Some("eval") {
+        print_eval(&args[2..].join(" "));
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("match") {
+        if let Err(err) = match_runner::run_match(&args) {
+            println!("{}", err);
+        }
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("build-book") {
+        if let Err(err) = book_builder::run_book_builder(&args) {
+            println!("{}", err);
+        }
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("tournament") {
+        if let Err(err) = tournament::run_tournament(&args) {
+            println!("{}", err);
+        }
+        return;
+    }
+
     let board = Board::start_pos();
     let mut cli_board = CliBoard::new(board);
+    match theme::Theme::from_args(&args) {
+        Ok(theme) => cli_board.set_theme(theme),
+        Err(err) => {
+            println!("{}", err);
+            return;
+        }
+    }
     // let en_passent_fen = "4k3/pppppppp/8/3P4/8/8/8/RNBQKBNR b KQkq - 0 1";
     // let mut cli_board = CliBoard::from_fen(en_passent_fen).unwrap();
 
-    // let white_player = RandomPlayer::new();
-    let white_player = HumanPlayer::new();
-    // let black_player = HumanPlayer::new();
-    let black_player = StoneFish::new(Player::Black, &cli_board.board());
+    // Select the players, like stonefish,selection=uct:0.7
+    let white_name = option_value(&args, "--white").unwrap_or("human");
+    let black_name = option_value(&args, "--black").unwrap_or("stonefish");
+    let players =
+        create_player(white_name, Player::White, &cli_board.board(), &args).and_then(|white| {
+            create_player(black_name, Player::Black, &cli_board.board(), &args)
+                .map(|black| (white, black))
+        });
+    let (mut white_player, mut black_player) = match players {
+        Ok(players) => players,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        }
+    };
+
+    // Humans look at the board from their side and may turn it around
+    if black_name == "human" && white_name != "human" {
+        cli_board.set_perspective(Player::Black);
+    }
+
+    // Training without seeing the board, the opponent is the side at the top
+    let blindfold = match option_value(&args, "--blindfold") {
+        None => None,
+        Some("all") => Some(Blindfold::AllPieces),
+        Some("opponent") if cli_board.is_flipped() => Some(Blindfold::Pieces(Player::White)),
+        Some("opponent") => Some(Blindfold::Pieces(Player::Black)),
+        Some("moves") => Some(Blindfold::MoveList),
+        Some(name) => {
+            println!("Unknown blindfold '{}', use all, opponent or moves.", name);
+            return;
+        }
+    };
+    cli_board.set_blindfold(blindfold);
+
+    // The full-screen mode shows everything on the board instead of printing it
+    let full_screen = args.iter().any(|arg| arg == "--tui");
+    let mut terminal = None;
+    if full_screen {
+        match tui::Terminal::enter() {
+            Ok(entered) => terminal = Some(entered),
+            Err(err) => {
+                println!("{}", err);
+                return;
+            }
+        }
+        cli_board.set_full_screen(true);
+        white_player.set_verbose(false);
+        black_player.set_verbose(false);
+    }
+
+    let cli_board = Arc::new(Mutex::new(cli_board));
+    white_player.set_display(Arc::clone(&cli_board));
+    black_player.set_display(Arc::clone(&cli_board));
+    if full_screen {
+        // Keep the clock and the analysis up to date
+        let ticker_board = Arc::clone(&cli_board);
+        thread::spawn(move || loop {
+            thread::sleep(tui::TICK);
+            let cli_board = ticker_board.lock().unwrap();
+            if !cli_board.is_full_screen() {
+                break;
+            }
+            cli_board.redraw();
+        });
+    } else if white_name == "human" || black_name == "human" {
+        println!(
+            "Enter moves like e2e4 or Nf3. Commands: /flip, /moves, /show e2, /attacks, /hanging, /clear."
+        );
+        if blindfold.is_some() {
+            println!("The position is hidden, /peek shows it until the next move.");
+        }
+    }
 
     let white_ref = Arc::new(Mutex::new(white_player));
     let black_ref = Arc::new(Mutex::new(black_player));
@@ -34,13 +176,18 @@ fn main() {
     let min_time = Duration::from_secs(10);
     let max_time = Duration::from_secs(300);
 
+    // The player who gave up the game, if any
+    let mut resigned = None;
     loop {
-        cli_board.color_print();
-        let board = cli_board.board();
+        let (board, board_state) = {
+            let mut cli_board = cli_board.lock().unwrap();
+            cli_board.color_print();
+            (cli_board.board(), cli_board.board_state())
+        };
         let before = SystemTime::now();
         let ponder_ref = Arc::new(Mutex::new(0));
 
-        match cli_board.board_state() {
+        match board_state {
             BoardState::Turn(player) => {
                 let (tx, rx) = mpsc::channel();
                 match player {
@@ -64,7 +211,11 @@ fn main() {
                         let bit_move = (*white_player).next_move(&board, time);
                         tx.send(bit_move).unwrap();
                         handle.join().unwrap();
-                        cli_board.apply_move(bit_move);
+                        if bit_move.is_null() {
+                            resigned = Some(player);
+                            break;
+                        }
+                        cli_board.lock().unwrap().apply_move(bit_move);
                     }
                     Player::Black => {
                         let th_board = board.clone();
@@ -85,22 +236,28 @@ fn main() {
                         let bit_move = (*black_player).next_move(&board, time);
                         tx.send(bit_move).unwrap();
                         handle.join().unwrap();
-                        cli_board.apply_move(bit_move);
+                        if bit_move.is_null() {
+                            resigned = Some(player);
+                            break;
+                        }
+                        cli_board.lock().unwrap().apply_move(bit_move);
                     }
                 }
             }
-            BoardState::Win(_) => break,
-            BoardState::Draw(_) => break,
+            BoardState::Win(_) | BoardState::Draw(_) => break,
         }
 
         let ponder_cnt = ponder_ref.lock().unwrap();
         let new_time = before.elapsed().unwrap();
-        println!(
-            "Time needed: {:02}m:{:02}s | Opponent ponders: {}",
-            new_time.as_secs() / 60,
-            new_time.as_secs() % 60,
-            *ponder_cnt
-        );
+        // The full screen has a clock instead
+        if !full_screen {
+            println!(
+                "Time needed: {:02}m:{:02}s | Opponent ponders: {}",
+                new_time.as_secs() / 60,
+                new_time.as_secs() % 60,
+                *ponder_cnt
+            );
+        }
         time = if new_time < min_time {
             min_time
         } else if new_time > max_time {
@@ -109,4 +266,26 @@ fn main() {
             new_time
         }
     }
+
+    // The game is over
+    let mut cli_board = cli_board.lock().unwrap();
+    let stats = cli_board.blindfold_stats_str();
+    // Reveal the final position
+    cli_board.set_blindfold(None);
+    if cli_board.is_full_screen() {
+        // Keep the final position on the normal screen
+        cli_board.set_full_screen(false);
+        drop(terminal.take());
+        cli_board.color_print();
+    } else if stats.is_some() {
+        cli_board.color_print();
+    }
+    if let Some(player) = resigned {
+        println!("{} resigned.", player);
+    }
+    // The whole game, which might not have fit next to the board
+    println!("{}", cli_board.move_list_str(cli_board::terminal_width()));
+    if let Some(stats) = stats {
+        println!("{}", stats);
+    }
 }
@@ -0,0 +1,482 @@
+use super::chess_player::ChessPlayer;
+use super::cli_board::{BoardState, DrawType};
+use super::elo::{MatchScore, Sprt, SprtVerdict};
+use super::pgn::{GameResult, PgnGame};
+use super::player_spec::{option_value, PlayerSpec};
+use super::zobrist;
+use pleco::{BitMove, Board, PieceType, Player};
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+/// The openings used without an opening file, as moves from the start position
+const DEFAULT_OPENINGS: [&str; 8] = [
+    "e2e4 e7e5 g1f3 b8c6",
+    "e2e4 c7c5 g1f3 d7d6",
+    "e2e4 e7e6 d2d4 d7d5",
+    "e2e4 c7c6 d2d4 d7d5",
+    "d2d4 d7d5 c2c4 e7e6",
+    "d2d4 g8f6 c2c4 g7g6",
+    "c2c4 e7e5 b1c3 g8f6",
+    "g1f3 d7d5 g2g3 g8f6",
+];
+/// The number of games without the `--games` option
+const DEFAULT_GAMES: usize = 10;
+/// The thinking time per move without the `--time` option, in milliseconds
+const DEFAULT_MOVE_TIME: u64 = 1000;
+/// The number of moves after which a game is adjudicated as a draw
+const DEFAULT_MAX_MOVES: usize = 200;
+
+/// The options shared by matches and tournaments
+pub const GAME_OPTIONS: &str =
+    "[--time ms] [--openings file] [--concurrency n] [--max-moves n] [--pgn file]";
+
+/// Reads a positive number option from the command line
+pub fn number_option(args: &[String], option: &str, default: usize) -> Result<usize, String> {
+    match option_value(args, option) {
+        Some(value) => value
+            .parse::<usize>()
+            .ok()
+            .filter(|value| *value > 0)
+            .ok_or(format!("Invalid value '{}' of {}.", value, option)),
+        None => Ok(default),
+    }
+}
+
+/// The configuration of the games between engines
+pub struct GameSettings {
+    /// The name of the event in the PGN
+    pub event: String,
+    /// The specs of the players, like `stonefish,selection=uct:0.7`
+    pub players: Vec<String>,
+    /// The thinking time of each move
+    pub move_time: Duration,
+    /// The start positions, each one is played twice with swapped colors
+    pub openings: Vec<Board>,
+    /// The number of games played at the same time
+    pub concurrency: usize,
+    /// The number of moves after which the game is a draw
+    pub max_moves: usize,
+    /// Where the games are saved
+    pub pgn_path: String,
+}
+
+impl GameSettings {
+    /// Reads the settings from the command line options, see `GAME_OPTIONS`
+    pub fn from_args(
+        event: String,
+        players: Vec<String>,
+        args: &[String],
+        default_pgn_path: &str,
+    ) -> Result<GameSettings, String> {
+        let concurrency = thread::available_parallelism()
+            .map(|cores| cores.get())
+            .unwrap_or(1);
+
+        let openings = match option_value(args, "--openings") {
+            Some(path) => load_openings(path)?,
+            None => DEFAULT_OPENINGS
+                .iter()
+                .map(|opening| parse_opening(opening))
+                .collect::<Result<Vec<Board>, String>>()?,
+        };
+
+        Ok(GameSettings {
+            event,
+            players,
+            move_time: Duration::from_millis(number_option(
+                args,
+                "--time",
+                DEFAULT_MOVE_TIME as usize,
+            )? as u64),
+            openings,
+            concurrency: number_option(args, "--concurrency", concurrency)?,
+            max_moves: number_option(args, "--max-moves", DEFAULT_MAX_MOVES)?,
+            pgn_path: option_value(args, "--pgn")
+                .unwrap_or(default_pgn_path)
+                .to_string(),
+        })
+    }
+
+    /// Checks that all players can be created before starting the games
+    pub fn check_players(&self, args: &[String]) -> Result<(), String> {
+        for spec in self.players.iter() {
+            PlayerSpec::parse(spec, args)?.create(Player::White, &Board::start_pos())?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses an opening, either as FEN or as moves from the start position like `e2e4 e7e5`
+pub fn parse_opening(line: &str) -> Result<Board, String> {
+    if line.contains('/') {
+        return Board::from_fen(line).map_err(|_| format!("Invalid opening fen '{}'.", line));
+    }
+    let mut board = Board::start_pos();
+    for uci_move in line.split_whitespace() {
+        if !board.apply_uci_move(uci_move) {
+            return Err(format!(
+                "Invalid move '{}' in opening '{}'.",
+                uci_move, line
+            ));
+        }
+    }
+    Ok(board)
+}
+
+/// Loads the openings of a file with one opening per line, `#` starts a comment
+pub fn load_openings(path: &str) -> Result<Vec<Board>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|err| format!("Can't read the openings '{}': {}", path, err))?;
+    let openings = text
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or("").trim())
+        .filter(|line| !line.is_empty())
+        .map(parse_opening)
+        .collect::<Result<Vec<Board>, String>>()?;
+    if openings.is_empty() {
+        return Err(format!("No openings found in '{}'.", path));
+    }
+    Ok(openings)
+}
+
+/// A game between two of the players
+#[derive(Debug, Clone, Copy)]
+pub struct ScheduledGame {
+    /// The number of the game, starting at 0
+    pub index: usize,
+    /// The index of the white player
+    pub white: usize,
+    /// The index of the black player
+    pub black: usize,
+    /// The index of the opening
+    pub opening: usize,
+}
+
+/// A finished game of a match or tournament
+pub struct FinishedGame {
+    pub game: ScheduledGame,
+    pub result: GameResult,
+    pub pgn: PgnGame,
+}
+
+impl FinishedGame {
+    /// The points of the player with the given index
+    pub fn score(&self, player: usize) -> f32 {
+        if player == self.game.white {
+            self.result.score(Player::White)
+        } else {
+            self.result.score(Player::Black)
+        }
+    }
+}
+
+/// Splits a board into the position before its first move and the moves played since.
+///
+/// A plain clone forgets how many moves it can undo, so the board is cloned in parallel.
+fn split_history(board: &Board) -> (Board, Vec<BitMove>) {
+    let mut start = board.parallel_clone();
+    let mut moves = Vec::new();
+    while let Some(mv) = start.last_move() {
+        moves.push(mv);
+        start.undo_move();
+    }
+    moves.reverse();
+    (start, moves)
+}
+
+/// Checks if neither player can possibly checkmate
+fn insufficient_material(board: &Board) -> bool {
+    let count = |piece_type| {
+        board.count_piece(Player::White, piece_type) + board.count_piece(Player::Black, piece_type)
+    };
+    count(PieceType::P) == 0
+        && count(PieceType::R) == 0
+        && count(PieceType::Q) == 0
+        && count(PieceType::N) + count(PieceType::B) <= 1
+}
+
+/// Plays a game without pondering, returns the moves, the result and why the game ended
+pub fn play_game(
+    white: &mut dyn ChessPlayer,
+    black: &mut dyn ChessPlayer,
+    start: &Board,
+    move_time: Duration,
+    max_moves: usize,
+) -> (Vec<BitMove>, GameResult, String) {
+    let mut board = start.clone();
+    let mut moves = Vec::new();
+    // Counts the occurrences of each position for the repetition draw
+    let mut positions: HashMap<u64, usize> = HashMap::new();
+    positions.insert(zobrist::key(&board), 1);
+
+    loop {
+        let player = match BoardState::of(&board) {
+            BoardState::Win(Player::White) => {
+                return (moves, GameResult::WhiteWins, String::from("checkmate"))
+            }
+            BoardState::Win(Player::Black) => {
+                return (moves, GameResult::BlackWins, String::from("checkmate"))
+            }
+            BoardState::Draw(DrawType::Stalemate) => {
+                return (moves, GameResult::Draw, String::from("stalemate"))
+            }
+            BoardState::Draw(DrawType::Rule50) => {
+                return (moves, GameResult::Draw, String::from("rule 50"))
+            }
+            BoardState::Turn(player) => player,
+        };
+        if insufficient_material(&board) {
+            return (
+                moves,
+                GameResult::Draw,
+                String::from("insufficient material"),
+            );
+        }
+        if moves.len() >= 2 * max_moves {
+            return (
+                moves,
+                GameResult::Draw,
+                format!("adjudicated after {} moves", max_moves),
+            );
+        }
+
+        let mv = match player {
+            Player::White => white.next_move(&board, move_time),
+            Player::Black => black.next_move(&board, move_time),
+        };
+        let loss = match player {
+            Player::White => GameResult::BlackWins,
+            Player::Black => GameResult::WhiteWins,
+        };
+        if mv.is_null() {
+            return (moves, loss, String::from("resignation"));
+        }
+        if !board.generate_moves().contains(&mv) {
+            // Illegal moves forfeit the game
+            return (moves, loss, format!("illegal move {}", mv.stringify()));
+        }
+        board.apply_move(mv);
+        moves.push(mv);
+
+        let occurrences = positions.entry(zobrist::key(&board)).or_insert(0);
+        *occurrences += 1;
+        if *occurrences >= 3 {
+            return (
+                moves,
+                GameResult::Draw,
+                String::from("threefold repetition"),
+            );
+        }
+    }
+}
+
+/// Plays the scheduled game, with fresh players
+fn play_scheduled_game(
+    settings: &GameSettings,
+    args: &[String],
+    game: ScheduledGame,
+) -> FinishedGame {
+    let start = &settings.openings[game.opening % settings.openings.len()];
+    let white_spec = &settings.players[game.white];
+    let black_spec = &settings.players[game.black];
+
+    // The specs have been checked before the games
+    let create = |spec: &str, player: Player| {
+        let mut chess_player = PlayerSpec::parse(spec, args)
+            .and_then(|spec| spec.create(player, start))
+            .expect("Invalid player spec");
+        chess_player.set_verbose(false);
+        chess_player
+    };
+    let mut white = create(white_spec, Player::White);
+    let mut black = create(black_spec, Player::Black);
+
+    let (moves, result, termination) = play_game(
+        &mut *white,
+        &mut *black,
+        start,
+        settings.move_time,
+        settings.max_moves,
+    );
+    // Record the opening moves in the PGN as well
+    let (pgn_start, opening_moves) = split_history(start);
+    FinishedGame {
+        game,
+        result,
+        pgn: PgnGame {
+            event: settings.event.clone(),
+            round: (game.index + 1).to_string(),
+            white: white_spec.clone(),
+            black: black_spec.clone(),
+            start: pgn_start,
+            moves: opening_moves.into_iter().chain(moves).collect(),
+            result: Some(result),
+            termination: Some(termination),
+            tags: Vec::new(),
+        },
+    }
+}
+
+/// Plays the scheduled games on multiple threads and reports each finished game.
+///
+/// No new games are started once `on_game` returns false.
+pub fn run_games<F>(
+    settings: GameSettings,
+    schedule: Vec<ScheduledGame>,
+    args: &[String],
+    mut on_game: F,
+) where
+    F: FnMut(FinishedGame) -> bool,
+{
+    let threads = settings.concurrency.min(schedule.len());
+    let settings = Arc::new(settings);
+    let schedule = Arc::new(schedule);
+    let args = Arc::new(args.to_vec());
+    let next_game = Arc::new(AtomicUsize::new(0));
+    let stop = Arc::new(AtomicBool::new(false));
+    let (tx, rx) = mpsc::channel();
+
+    let mut handles = Vec::new();
+    for _ in 0..threads {
+        let th_settings = Arc::clone(&settings);
+        let th_schedule = Arc::clone(&schedule);
+        let th_args = Arc::clone(&args);
+        let th_next_game = Arc::clone(&next_game);
+        let th_stop = Arc::clone(&stop);
+        let th_tx = tx.clone();
+        handles.push(thread::spawn(move || {
+            while !th_stop.load(Ordering::Relaxed) {
+                let index = th_next_game.fetch_add(1, Ordering::Relaxed);
+                let game = match th_schedule.get(index) {
+                    Some(game) => *game,
+                    None => break,
+                };
+                let finished = play_scheduled_game(&th_settings, &th_args, game);
+                if th_tx.send(finished).is_err() {
+                    break;
+                }
+            }
+        }));
+    }
+    drop(tx);
+
+    for game in rx {
+        if !on_game(game) {
+            // Let the running games finish, but don't start new ones
+            stop.store(true, Ordering::Relaxed);
+        }
+    }
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+/// Creates the file the games are saved to
+pub fn create_pgn_file(path: &str) -> Result<File, String> {
+    File::create(path).map_err(|err| format!("Can't create '{}': {}", path, err))
+}
+
+/// Plays a match between two players, like
+/// `match stonefish random --games 20 --time 500 --sprt 0,50`
+pub fn run_match(args: &[String]) -> Result<(), String> {
+    let usage = format!(
+        "Usage: match <player> <player> [--games n] [--sprt elo0,elo1[,alpha,beta]] {}",
+        GAME_OPTIONS
+    );
+    let names = match (args.get(2), args.get(3)) {
+        (Some(first), Some(second)) if !first.starts_with("--") && !second.starts_with("--") => {
+            vec![first.clone(), second.clone()]
+        }
+        _ => return Err(usage),
+    };
+    let games = number_option(args, "--games", DEFAULT_GAMES)
+        .map_err(|err| format!("{}\n{}", err, usage))?;
+    let sprt = match option_value(args, "--sprt") {
+        Some(spec) => Some(Sprt::parse(spec).ok_or(format!(
+            "Invalid SPRT '{}'.\nUse elo0,elo1[,alpha,beta] with elo0 < elo1.",
+            spec
+        ))?),
+        None => None,
+    };
+    let settings = GameSettings::from_args(
+        format!("{} vs {}", names[0], names[1]),
+        names.clone(),
+        args,
+        "match.pgn",
+    )
+    .map_err(|err| format!("{}\n{}", err, usage))?;
+    settings.check_players(args)?;
+    let mut pgn_file = create_pgn_file(&settings.pgn_path)?;
+    let pgn_path = settings.pgn_path.clone();
+
+    println!(
+        "{} vs {}: {} games, {}ms per move, {} openings, {} at a time",
+        names[0],
+        names[1],
+        games,
+        settings.move_time.as_millis(),
+        settings.openings.len(),
+        settings.concurrency.min(games)
+    );
+
+    // The players swap colors every game and play each opening with both colors
+    let schedule = (0..games)
+        .map(|index| ScheduledGame {
+            index,
+            white: index % 2,
+            black: 1 - index % 2,
+            opening: index / 2,
+        })
+        .collect();
+
+    let mut score = MatchScore::default();
+    let mut write_error = None;
+    run_games(settings, schedule, args, |game| {
+        score.add(game.score(0));
+        println!(
+            "Game {}: {} vs {} {} ({}) | {} {}",
+            game.game.index + 1,
+            game.pgn.white,
+            game.pgn.black,
+            game.result.pgn_str(),
+            game.pgn.termination.as_deref().unwrap_or(""),
+            names[0],
+            score
+        );
+        if let Err(err) = writeln!(pgn_file, "{}", game.pgn) {
+            write_error = Some(err);
+        }
+        match sprt {
+            Some(sprt) => sprt.verdict(&score) == SprtVerdict::Continue,
+            None => true,
+        }
+    });
+
+    println!();
+    println!(
+        "Score of {} vs {}: {} after {} games",
+        names[0],
+        names[1],
+        score,
+        score.games()
+    );
+    if let Some((elo, margin)) = score.elo() {
+        println!("Elo difference: {:+.1} +/- {:.1}", elo, margin);
+    }
+    if let Some(sprt) = sprt {
+        println!("{}", sprt.info_str(&score));
+    }
+    match write_error {
+        Some(err) => Err(format!("Can't save the games to '{}': {}", pgn_path, err)),
+        None => {
+            println!("Games saved to {}", pgn_path);
+            Ok(())
+        }
+    }
+}
@@ -1,497 +1,934 @@
-use pleco::{BitMove, Board, MoveList, Player};
-
-use rand::{self, rngs::ThreadRng, Rng};
-
-use std::cmp::{Ordering, PartialEq};
-use std::ops::{Add, AddAssign};
-use std::sync::mpsc;
-use std::thread;
-
-const PARALLEL_SIMULATIONS: usize = 5;
-const PARALLEL_PLAYOUTS: usize = 5;
-
-#[derive(Debug)]
-/// The result of a simulation step
-pub struct SimResult {
-    wins: usize,
-    playouts: usize,
-}
-
-impl SimResult {
-    /// Invert the simulation result
-    pub fn invert(&self) -> SimResult {
-        let losses = self.playouts - self.wins;
-        SimResult {
-            wins: losses,
-            playouts: self.playouts,
-        }
-    }
-}
-
-impl PartialEq for SimResult {
-    fn eq(&self, other: &Self) -> bool {
-        self.playouts == other.playouts && self.wins == other.wins
-    }
-}
-
-impl Add for SimResult {
-    type Output = Self;
-
-    fn add(self, other: Self) -> Self {
-        Self {
-            wins: self.wins + other.wins,
-            playouts: self.playouts + other.playouts,
-        }
-    }
-}
-
-impl AddAssign for SimResult {
-    fn add_assign(&mut self, other: Self) {
-        *self = Self {
-            wins: self.wins + other.wins,
-            playouts: self.playouts + other.playouts,
-        };
-    }
-}
-
-/// The end of a game
-pub enum PlayEnd {
-    Win,
-    Loss,
-}
-
-/// The result of a play
-pub enum PlayResult {
-    End(PlayEnd),
-    Moves(MoveList),
-}
-
-impl PlayResult {
-    /// Determines the result of a board
-    pub fn get_result(board: &Board, player: Player) -> PlayResult {
-        let moves = board.generate_moves();
-
-        // Rule 50
-        if board.rule_50() >= 50 {
-            // The game is a draw
-            PlayResult::End(PlayResult::get_draw_result())
-        } else if moves.len() == 0 {
-            if board.checkmate() {
-                // One player wins
-                if player == board.turn() {
-                    // The player is in checkmate, the player loses
-                    PlayResult::End(PlayEnd::Loss)
-                } else {
-                    // The opponent is in checkmate, the player wins
-                    PlayResult::End(PlayEnd::Win)
-                }
-            } else {
-                // The game is a draw
-                PlayResult::End(PlayResult::get_draw_result())
-            }
-        } else {
-            // There are moves left to play
-            PlayResult::Moves(moves)
-        }
-    }
-
-    /// Determines the result of a draw
-    pub fn get_draw_result() -> PlayEnd {
-        // Choose random outcome
-        let mut rng = rand::thread_rng();
-        let rnd = rng.gen_range(0, 2);
-        if rnd == 0 {
-            // Win with 50% chance
-            PlayEnd::Win
-        } else {
-            PlayEnd::Loss
-        }
-    }
-}
-
-/// A move to the next node
-pub struct MCTreeMove {
-    /// The move to reach the node
-    pub mv: BitMove,
-    /// The next node
-    pub node: MCTree,
-}
-
-impl Clone for MCTreeMove {
-    fn clone(&self) -> Self {
-        MCTreeMove {
-            mv: self.mv,
-            node: self.node.clone(),
-        }
-    }
-}
-
-impl MCTreeMove {
-    /// Creates a new MCTreeMove
-    pub fn new(mv: BitMove, state: &Board) -> MCTreeMove {
-        MCTreeMove {
-            mv: mv,
-            node: MCTree::new(state),
-        }
-    }
-    /// Compares the play value of the two moves
-    pub fn cmp_play_value(&self, other: &MCTreeMove) -> Ordering {
-        let self_value = self.node.play_value();
-        let other_value = other.node.play_value();
-
-        if self_value < other_value {
-            Ordering::Less
-        } else if self_value > other_value {
-            Ordering::Greater
-        } else {
-            Ordering::Equal
-        }
-    }
-
-    /// Determines the move with the maximum play value
-    pub fn max_play(moves: &Vec<MCTreeMove>) -> Option<&MCTreeMove> {
-        moves.iter().max_by(|a, b| a.cmp_play_value(b))
-    }
-
-    /// Determines the move with the maximum play value
-    pub fn max_play_mut(
-        moves: &mut Vec<MCTreeMove>,
-        parent_playouts: usize,
-    ) -> Option<&mut MCTreeMove> {
-        moves.iter_mut().max_by(|a, b| a.cmp_play_value(b))
-    }
-
-    /// Compares the selection value of the two plays
-    pub fn cmp_select_value(&self, other: &MCTreeMove, parent_playouts: usize) -> Ordering {
-        let self_value = self.node.select_value(parent_playouts);
-        let other_value = other.node.select_value(parent_playouts);
-
-        if self_value < other_value {
-            Ordering::Less
-        } else if self_value > other_value {
-            Ordering::Greater
-        } else {
-            Ordering::Equal
-        }
-    }
-
-    /// Determines the move with the maximum select value
-    pub fn max_select(moves: &Vec<MCTreeMove>, parent_playouts: usize) -> Option<&MCTreeMove> {
-        moves
-            .iter()
-            .max_by(|a, b| a.cmp_select_value(b, parent_playouts))
-    }
-
-    /// Determines the move with the maximum select value
-    pub fn max_select_mut(
-        moves: &mut Vec<MCTreeMove>,
-        parent_playouts: usize,
-    ) -> Option<&mut MCTreeMove> {
-        moves
-            .iter_mut()
-            .max_by(|a, b| a.cmp_select_value(b, parent_playouts))
-    }
-}
-
-/// Monte-Carlo Tree
-pub struct MCTree {
-    /// The current state
-    pub state: Board,
-    /// The number of wins for this state
-    pub wins: usize,
-    /// The number of playouts for this state
-    pub playouts: usize,
-    /// The children for this state
-    pub children: Vec<MCTreeMove>,
-}
-
-impl Clone for MCTree {
-    fn clone(&self) -> Self {
-        MCTree {
-            state: self.state.clone(),
-            wins: self.wins,
-            playouts: self.playouts,
-            children: self.children.clone(),
-        }
-    }
-}
-
-impl MCTree {
-    /// Creates a new MCTree
-    pub fn new(state: &Board) -> MCTree {
-        // Get the next board
-        let state = state.clone();
-
-        MCTree {
-            state: state,
-            wins: 0,              // No wins yet
-            playouts: 0,          // No playouts yet
-            children: Vec::new(), // No children yet
-        }
-    }
-
-    /// The player to consider for this node
-    pub fn player(&self) -> Player {
-        self.state.turn()
-    }
-
-    pub fn assert_valid(&self) {
-        if !self.is_leaf() {
-            // Validate playout results
-            let mut sum_result = SimResult {
-                wins: 0,
-                playouts: 0,
-            };
-            for child in &self.children {
-                let node = &child.node;
-                sum_result += SimResult {
-                    wins: node.wins,
-                    playouts: node.playouts,
-                }
-                .invert();
-
-                // Player must be the opposite
-                assert_ne!(
-                    node.player(), self.player(),
-                    "The player must switch every move!"
-                );
-                // Validate children
-                node.assert_valid();
-            }
-            assert_eq!(
-                true,
-                self.wins >= sum_result.wins && self.playouts >= sum_result.playouts,
-                "This node must have eq or more playouts than its children!"
-            );
-        }
-    }
-
-    pub fn info_str(&self) -> String {
-        // Self info
-        let size = self.size();
-        let height = self.height();
-        let width = self.children.len();
-        let wins = self.wins;
-        let playouts = self.playouts;
-        let winrate = (1. - self.play_value()) * 100.; // Inverted for this players
-        let s = format!(
-            "s:{}, h:{}, w:{}, {}/{} ({:05.1}%)",
-            size, height, width, wins, playouts, winrate
-        );
-
-        let best_mv = self.best_move();
-        match best_mv {
-            Option::Some(mv) => {
-                // Best move info
-                let node = &mv.node;
-                let mv_playouts = node.playouts;
-                let mv_wins = mv_playouts - node.wins; // Inverted for this player
-                // Calculate avg winrate of the available moves
-                let mut sum_winrate = 0.;
-                for child in &self.children {
-                    sum_winrate += child.node.play_value();
-                }
-                let avg_winrate = sum_winrate / width as f32 * 100.;
-                let mv_winrate = node.play_value() * 100.;
-                let win_dif = mv_winrate - winrate;
-                let avg_win_dif = mv_winrate - avg_winrate;
-                format!(
-                    "{} | {}/{} ({:05.1}%) => {:+.1}% | avg {:+.1}%",
-                    s, mv_wins, mv_playouts, mv_winrate, win_dif, avg_win_dif
-                )
-            }
-            Option::None => s,
-        }
-    }
-
-    /// Determines the size of the tree
-    pub fn size(&self) -> usize {
-        let mut size = 1;
-        for mv_node in &self.children {
-            // Recursively add the size of the child nodes
-            size += mv_node.node.size();
-        }
-        size
-    }
-
-    /// Determine the height of the tree
-    pub fn height(&self) -> usize {
-        if self.is_leaf() {
-            // A leaf node has height 0
-            0
-        } else {
-            // Determine the maximum height of its child nodes
-            let mut max_height = 0;
-            for child in &self.children {
-                if child.node.height() > max_height {
-                    max_height = child.node.height();
-                }
-            }
-            1 + max_height
-        }
-    }
-
-    /// Gets the best move, if available
-    pub fn best_move(&self) -> Option<&MCTreeMove> {
-        // Select the most promising move
-        MCTreeMove::max_play(&self.children)
-    }
-
-    /// Updates the current node with the given result
-    pub fn update(&mut self, result: &SimResult) {
-        self.playouts += result.playouts;
-        self.wins += result.wins;
-    }
-
-    pub fn to_string(&self) -> String {
-        format!(
-            "{}/{} ({:05.1}%)",
-            self.wins,
-            self.playouts,
-            (1. - self.play_value()) * 100.
-        )
-    }
-
-    /// Selects the next node to expand
-    pub fn select(&mut self) -> SimResult {
-        if self.is_leaf() {
-            // Leaf nodes can be expanded
-            let result = self.expand();
-            // Backtrack result
-            result
-        } else {
-            // Select the most promising child node
-            let playouts = self.playouts;
-            let best_selection = MCTreeMove::max_select_mut(&mut self.children, playouts).unwrap();
-            // The child node has the opposite player, invert the result
-            let result = best_selection.node.select().invert();
-            // Update the node
-            self.update(&result);
-            // Backtrack result
-            result
-        }
-    }
-
-    /// Expands and update the selected node
-    pub fn expand(&mut self) -> SimResult {
-        let play_result = PlayResult::get_result(&self.state, self.player());
-
-        // Generate child nodes if necessary
-        match play_result {
-            // There are still moves to make
-            PlayResult::Moves(moves) => {
-                // Generate child nodes
-                for mv in moves {
-                    let mut new_state = self.state.clone();
-                    new_state.apply_move(mv);
-                    let node = MCTreeMove::new(mv, &new_state);
-                    self.children.push(node);
-                }
-                // Perform simulations
-                let mut result = SimResult {
-                    wins: 0,
-                    playouts: 0,
-                };
-                let mut rng = rand::thread_rng();
-                for _ in 0..PARALLEL_SIMULATIONS {
-                    // Select a child node for simulation
-                    let rnd = rng.gen_range(0 as usize, self.children.len());
-                    // Make a simulation step
-                    let child_result = self.children[rnd].node.simulate().invert();
-                    result += child_result;
-                }
-                self.update(&result);
-                result
-            }
-            // This node is the end of the game, simulate it
-            PlayResult::End(_) => self.simulate(),
-        }
-    }
-
-    /// Makes a simulation step for this move
-    pub fn simulate(&mut self) -> SimResult {
-        let playouts = PARALLEL_PLAYOUTS;
-        let (tx, rx) = mpsc::channel();
-        // Perform playouts in parallel
-        for _ in 0..playouts {
-            let board = self.state.clone();
-            let tx = tx.clone();
-            thread::spawn(move || {
-                let result = MCTree::single_playout(board);
-                tx.send(result).unwrap();
-            });
-        }
-
-        let mut wins = 0;
-
-        // Aggregate results
-        for _ in 0..playouts {
-            let result = rx.recv().unwrap();
-            match result {
-                PlayEnd::Win => wins += 1,
-                PlayEnd::Loss => (),
-            }
-        }
-        let result = SimResult {
-            playouts: playouts,
-            wins: wins,
-        };
-        self.update(&result);
-        result
-    }
-
-    /// Performs a singular playout
-    fn single_playout(board: Board) -> PlayEnd {
-        let mut board = board.clone();
-        let player = board.turn();
-        // Simulate
-        loop {
-            // Check for game end
-            let result = PlayResult::get_result(&board, player);
-
-            match result {
-                PlayResult::Moves(moves) => {
-                    // Choose random move
-                    let mut rng = rand::thread_rng();
-                    let rnd = rng.gen_range(0 as usize, moves.len());
-                    let mv = moves[rnd];
-                    // Playout with that move
-                    board.apply_move(mv);
-                }
-                PlayResult::End(end) => {
-                    // The game ended, return the results
-                    return end;
-                }
-            }
-        }
-    }
-
-    /// Determines if the node is a leaf node.
-    pub fn is_leaf(&self) -> bool {
-        self.children.len() == 0
-    }
-
-    /// Determines how valuable it is to play this move.
-    pub fn play_value(&self) -> f32 {
-        if self.playouts == 0 {
-            0.5
-        } else {
-            // Determine 'winrate', but for the opponent
-            1. - (self.wins as f32) / (self.playouts as f32)
-        }
-    }
-
-    /// Determines how valuable it is to expand this node.
-    pub fn select_value(&self, parent_playouts: usize) -> f32 {
-        // Exploitation: Exploit potentially good moves.
-        let exploitation = self.play_value();
-        // Exploration: Explore rarely investigated moves.
-        let exploration = if self.playouts == 0 {
-            1.
-        } else {
-            let exploration_factor = 1.4142; // sqrt(2)
-            exploration_factor * ((parent_playouts as f32).ln() / (self.playouts as f32)).sqrt()
-        };
-        exploitation + exploration
-    }
-}
+use super::endgame;
+use super::eval::{evaluate, win_probability};
+use super::playout::{PlayoutPolicy, PlayoutStats, RandomPlayouts};
+use super::priors::{HeuristicPriors, PriorPolicy, UniformPriors};
+use super::selection::{SelectionPolicy, Uct};
+use super::zobrist;
+use pleco::{BitMove, Board, MoveList, Player};
+
+use rand::{self, rngs::ThreadRng, Rng};
+
+use std::cmp::{Ordering, PartialEq};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::{Add, AddAssign};
+use std::sync::{mpsc, Arc, Mutex, Weak};
+use std::thread;
+
+const PARALLEL_SIMULATIONS: usize = 5;
+const PARALLEL_PLAYOUTS: usize = 5;
+/// The default number of moves after which a playout is evaluated statically
+const PLAYOUT_DEPTH: usize = 40;
+
+#[derive(Debug)]
+/// The result of a simulation step
+pub struct SimResult {
+    wins: usize,
+    playouts: usize,
+}
+
+impl SimResult {
+    /// Invert the simulation result
+    pub fn invert(&self) -> SimResult {
+        let losses = self.playouts - self.wins;
+        SimResult {
+            wins: losses,
+            playouts: self.playouts,
+        }
+    }
+
+    /// The result of playouts from a proven node
+    pub fn from_proof(proof: Proof, playouts: usize) -> SimResult {
+        let wins = match proof {
+            Proof::Win(_) => playouts,
+            Proof::Loss(_) => 0,
+            Proof::Draw => (0..playouts)
+                .filter(|_| match PlayResult::get_draw_result() {
+                    PlayEnd::Win => true,
+                    PlayEnd::Loss => false,
+                })
+                .count(),
+        };
+        SimResult { wins, playouts }
+    }
+}
+
+impl PartialEq for SimResult {
+    fn eq(&self, other: &Self) -> bool {
+        self.playouts == other.playouts && self.wins == other.wins
+    }
+}
+
+impl Add for SimResult {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            wins: self.wins + other.wins,
+            playouts: self.playouts + other.playouts,
+        }
+    }
+}
+
+impl AddAssign for SimResult {
+    fn add_assign(&mut self, other: Self) {
+        *self = Self {
+            wins: self.wins + other.wins,
+            playouts: self.playouts + other.playouts,
+        };
+    }
+}
+
+/// The end of a game
+pub enum PlayEnd {
+    Win,
+    Loss,
+}
+
+/// A game-theoretic value proven by the search, for the player to move.
+///
+/// Wins and losses store the number of plies until checkmate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Proof {
+    Win(usize),
+    Loss(usize),
+    Draw,
+}
+
+impl Proof {
+    /// The proof of a finished game
+    pub fn from_end(board: &Board) -> Proof {
+        if board.checkmate() {
+            // The player to move is in checkmate
+            Proof::Loss(0)
+        } else {
+            Proof::Draw
+        }
+    }
+
+    /// Describes the proof from the perspective of the player to move
+    pub fn describe(&self) -> String {
+        match *self {
+            Proof::Win(plies) => format!("mate in {}", plies.div_ceil(2)),
+            Proof::Loss(plies) => format!("mated in {}", plies.div_ceil(2)),
+            Proof::Draw => String::from("proven draw"),
+        }
+    }
+}
+
+/// The result of a play
+#[allow(clippy::large_enum_variant)]
+pub enum PlayResult {
+    End(PlayEnd),
+    Moves(MoveList),
+}
+
+impl PlayResult {
+    /// Determines the result of a board
+    pub fn get_result(board: &Board, player: Player) -> PlayResult {
+        let moves = board.generate_moves();
+
+        if moves.is_empty() {
+            if board.checkmate() {
+                // One player wins
+                if player == board.turn() {
+                    // The player is in checkmate, the player loses
+                    PlayResult::End(PlayEnd::Loss)
+                } else {
+                    // The opponent is in checkmate, the player wins
+                    PlayResult::End(PlayEnd::Win)
+                }
+            } else {
+                // The game is a draw
+                PlayResult::End(PlayResult::get_draw_result())
+            }
+        } else if board.rule_50() >= 50 || endgame::known_draw(board) {
+            // Rule 50, or an endgame that can't be won
+            PlayResult::End(PlayResult::get_draw_result())
+        } else {
+            // There are moves left to play
+            PlayResult::Moves(moves)
+        }
+    }
+
+    /// Determines the result of a draw
+    pub fn get_draw_result() -> PlayEnd {
+        // Choose random outcome
+        let mut rng = rand::thread_rng();
+        let rnd = rng.gen_range(0, 2);
+        if rnd == 0 {
+            // Win with 50% chance
+            PlayEnd::Win
+        } else {
+            PlayEnd::Loss
+        }
+    }
+}
+
+/// The key of a node in the transposition table.
+///
+/// The ply is part of the key, so that a node can never be its own descendant.
+/// The halfmove clock is part of it too, because the rule 50 decides the proofs of a node.
+/// The search ignores repetitions, so nothing else of the history matters.
+pub type NodeKey = (u64, u16, i16);
+
+/// A node shared between all parents that transpose into it
+pub type MCTreeRef = Arc<Mutex<MCTree>>;
+
+/// Table of all nodes in the tree, keyed by their position.
+///
+/// Transposed positions map to the same node, which turns the tree into a DAG.
+pub struct TranspositionTable {
+    /// The nodes in the tree
+    nodes: HashMap<NodeKey, Weak<Mutex<MCTree>>>,
+    /// The number of times an existing node was reused
+    pub hits: usize,
+}
+
+impl TranspositionTable {
+    /// Creates a new, empty TranspositionTable
+    pub fn new() -> TranspositionTable {
+        TranspositionTable {
+            nodes: HashMap::new(),
+            hits: 0,
+        }
+    }
+
+    /// Gets the node for the given state, creating it if it doesn't exist yet
+    pub fn get_or_insert(&mut self, state: &Board) -> MCTreeRef {
+        let key = MCTree::state_key(state);
+
+        if let Some(node) = self.nodes.get(&key).and_then(Weak::upgrade) {
+            // Transposition, share the existing node
+            self.hits += 1;
+            node
+        } else {
+            let node = Arc::new(Mutex::new(MCTree::new(state)));
+            self.nodes.insert(key, Arc::downgrade(&node));
+            node
+        }
+    }
+
+    /// Removes the nodes that are no longer part of the tree
+    pub fn prune(&mut self) {
+        self.nodes.retain(|_, node| node.upgrade().is_some());
+    }
+}
+
+/// The configuration of a search
+pub struct SearchSettings {
+    /// The policy to select the next node to expand
+    pub selection: Box<dyn SelectionPolicy>,
+    /// The policy to determine the priors of new moves
+    pub priors: Box<dyn PriorPolicy>,
+    /// The policy to choose the moves of playouts
+    pub playouts: Arc<dyn PlayoutPolicy>,
+    /// Statistics about the playouts so far
+    pub playout_stats: Arc<PlayoutStats>,
+    /// The number of moves after which a playout is evaluated statically, if any
+    pub playout_depth: Option<usize>,
+}
+
+impl Default for SearchSettings {
+    fn default() -> Self {
+        SearchSettings {
+            selection: Box::new(Uct::default()),
+            priors: Box::new(HeuristicPriors::default()),
+            playouts: Arc::new(RandomPlayouts::new()),
+            playout_stats: Arc::new(PlayoutStats::new()),
+            playout_depth: Some(PLAYOUT_DEPTH),
+        }
+    }
+}
+
+/// A move to the next node
+pub struct MCTreeMove {
+    /// The move to reach the node
+    pub mv: BitMove,
+    /// The number of playouts backed up through this move
+    pub playouts: usize,
+    /// The prior probability that this is the best move
+    pub prior: f32,
+    /// The next node, possibly shared with other parents
+    pub node: MCTreeRef,
+}
+
+impl Clone for MCTreeMove {
+    fn clone(&self) -> Self {
+        MCTreeMove {
+            mv: self.mv,
+            playouts: self.playouts,
+            prior: self.prior,
+            node: Arc::clone(&self.node),
+        }
+    }
+}
+
+impl MCTreeMove {
+    /// Creates a new MCTreeMove
+    pub fn new(mv: BitMove, prior: f32, node: MCTreeRef) -> MCTreeMove {
+        MCTreeMove {
+            mv,
+            playouts: 0,
+            prior,
+            node,
+        }
+    }
+
+    /// Determines how valuable it is to play this move.
+    pub fn play_value(&self) -> f32 {
+        self.node.lock().unwrap().play_value()
+    }
+
+    /// Determines how valuable it is to play this move, preferring proven results.
+    ///
+    /// Quick wins and slow losses are preferred.
+    pub fn proven_play_value(&self) -> f32 {
+        let node = self.node.lock().unwrap();
+        match node.proof {
+            // The opponent loses
+            Option::Some(Proof::Loss(plies)) => 2. + 1. / (plies + 1) as f32,
+            // The opponent wins
+            Option::Some(Proof::Win(plies)) => -1. - 1. / (plies + 1) as f32,
+            Option::Some(Proof::Draw) => 0.5,
+            Option::None => node.play_value(),
+        }
+    }
+
+    /// Compares the play value of the two moves
+    pub fn cmp_play_value(&self, other: &MCTreeMove) -> Ordering {
+        let self_value = self.proven_play_value();
+        let other_value = other.proven_play_value();
+
+        if self_value < other_value {
+            Ordering::Less
+        } else if self_value > other_value {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }
+    }
+
+    /// Determines the move with the maximum play value
+    pub fn max_play(moves: &[MCTreeMove]) -> Option<&MCTreeMove> {
+        moves.iter().max_by(|a, b| a.cmp_play_value(b))
+    }
+
+    /// Determines the move with the maximum play value
+    #[allow(dead_code)]
+    pub fn max_play_mut(
+        moves: &mut [MCTreeMove],
+        _parent_playouts: usize,
+    ) -> Option<&mut MCTreeMove> {
+        moves.iter_mut().max_by(|a, b| a.cmp_play_value(b))
+    }
+
+    /// Determines how valuable it is to expand the node of this move.
+    pub fn select_value(&self, parent_playouts: usize, policy: &dyn SelectionPolicy) -> f32 {
+        let node = self.node.lock().unwrap();
+        match node.proof {
+            // Always back up a proven win
+            Option::Some(Proof::Loss(_)) => f32::INFINITY,
+            // Never waste time on a proven loss
+            Option::Some(Proof::Win(_)) => f32::NEG_INFINITY,
+            _ => policy.select_value(self, &node, parent_playouts),
+        }
+    }
+
+    /// Compares the selection value of the two plays
+    pub fn cmp_select_value(
+        &self,
+        other: &MCTreeMove,
+        parent_playouts: usize,
+        policy: &dyn SelectionPolicy,
+    ) -> Ordering {
+        let self_value = self.select_value(parent_playouts, policy);
+        let other_value = other.select_value(parent_playouts, policy);
+
+        if self_value < other_value {
+            Ordering::Less
+        } else if self_value > other_value {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }
+    }
+
+    /// Determines the move with the maximum select value
+    #[allow(dead_code)]
+    pub fn max_select<'a>(
+        moves: &'a [MCTreeMove],
+        parent_playouts: usize,
+        policy: &dyn SelectionPolicy,
+    ) -> Option<&'a MCTreeMove> {
+        moves
+            .iter()
+            .max_by(|a, b| a.cmp_select_value(b, parent_playouts, policy))
+    }
+
+    /// Determines the move with the maximum select value
+    pub fn max_select_mut<'a>(
+        moves: &'a mut [MCTreeMove],
+        parent_playouts: usize,
+        policy: &dyn SelectionPolicy,
+    ) -> Option<&'a mut MCTreeMove> {
+        moves
+            .iter_mut()
+            .max_by(|a, b| a.cmp_select_value(b, parent_playouts, policy))
+    }
+}
+
+/// Monte-Carlo Tree
+pub struct MCTree {
+    /// The current state
+    pub state: Board,
+    /// The number of wins for this state
+    pub wins: usize,
+    /// The number of playouts for this state
+    pub playouts: usize,
+    /// The children for this state
+    pub children: Vec<MCTreeMove>,
+    /// The proven result of this state, if known
+    pub proof: Option<Proof>,
+}
+
+impl Clone for MCTree {
+    fn clone(&self) -> Self {
+        MCTree {
+            state: self.state.clone(),
+            wins: self.wins,
+            playouts: self.playouts,
+            children: self.children.clone(),
+            proof: self.proof,
+        }
+    }
+}
+
+impl fmt::Display for MCTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} ({:05.1}%)",
+            self.wins,
+            self.playouts,
+            (1. - self.play_value()) * 100.
+        )
+    }
+}
+
+impl MCTree {
+    /// Creates a new MCTree
+    pub fn new(state: &Board) -> MCTree {
+        // Get the next board
+        let state = state.clone();
+
+        MCTree {
+            state,
+            wins: 0,              // No wins yet
+            playouts: 0,          // No playouts yet
+            children: Vec::new(), // No children yet
+            proof: None,          // Nothing proven yet
+        }
+    }
+
+    /// The transposition key of the given state
+    pub fn state_key(state: &Board) -> NodeKey {
+        (zobrist::key(state), state.moves_played(), state.rule_50())
+    }
+
+    /// The transposition key of this node
+    pub fn key(&self) -> NodeKey {
+        MCTree::state_key(&self.state)
+    }
+
+    /// The player to consider for this node
+    pub fn player(&self) -> Player {
+        self.state.turn()
+    }
+
+    pub fn assert_valid(&self) {
+        let mut visited = HashSet::new();
+        visited.insert(self.key());
+        self.assert_valid_visited(&mut visited);
+    }
+
+    fn assert_valid_visited(&self, visited: &mut HashSet<NodeKey>) {
+        if !self.is_leaf() {
+            // Validate playout results
+            let mut sum_playouts = 0;
+            for child in &self.children {
+                let node = child.node.lock().unwrap();
+                sum_playouts += child.playouts;
+
+                // Player must be the opposite
+                assert_ne!(
+                    node.player(),
+                    self.player(),
+                    "The player must switch every move!"
+                );
+                // Shared nodes also count the playouts of their other parents
+                assert!(
+                    node.playouts >= child.playouts,
+                    "A node must have eq or more playouts than its moves!"
+                );
+                // Validate children, but shared nodes only once
+                if visited.insert(node.key()) {
+                    node.assert_valid_visited(visited);
+                }
+            }
+            assert!(
+                self.playouts >= sum_playouts,
+                "This node must have eq or more playouts than its children!"
+            );
+        }
+    }
+
+    pub fn info_str(&self, table: &TranspositionTable) -> String {
+        // Self info
+        let size = self.size();
+        let height = self.height();
+        let width = self.children.len();
+        let wins = self.wins;
+        let playouts = self.playouts;
+        let winrate = (1. - self.play_value()) * 100.; // Inverted for this players
+        let s = format!(
+            "s:{}, h:{}, w:{}, tt:{}, {}/{} ({:05.1}%)",
+            size, height, width, table.hits, wins, playouts, winrate
+        );
+
+        let s = match self.proof {
+            Option::Some(proof) => format!("{} | {}", s, proof.describe()),
+            Option::None => s,
+        };
+
+        let best_mv = self.best_move();
+        match best_mv {
+            Option::Some(mv) => {
+                // Best move info
+                let node = mv.node.lock().unwrap();
+                let mv_playouts = node.playouts;
+                let mv_wins = mv_playouts - node.wins; // Inverted for this player
+                                                       // Calculate avg winrate of the available moves
+                let mut sum_winrate = 0.;
+                for child in &self.children {
+                    if Arc::ptr_eq(&child.node, &mv.node) {
+                        // Already locked
+                        sum_winrate += node.play_value();
+                    } else {
+                        sum_winrate += child.play_value();
+                    }
+                }
+                let avg_winrate = sum_winrate / width as f32 * 100.;
+                let mv_winrate = node.play_value() * 100.;
+                let win_dif = mv_winrate - winrate;
+                let avg_win_dif = mv_winrate - avg_winrate;
+                format!(
+                    "{} | {}/{} ({:05.1}%) => {:+.1}% | avg {:+.1}%",
+                    s, mv_wins, mv_playouts, mv_winrate, win_dif, avg_win_dif
+                )
+            }
+            Option::None => s,
+        }
+    }
+
+    /// Determines the size of the tree, counting shared nodes once
+    pub fn size(&self) -> usize {
+        let mut visited = HashSet::new();
+        visited.insert(self.key());
+        self.size_visited(&mut visited)
+    }
+
+    fn size_visited(&self, visited: &mut HashSet<NodeKey>) -> usize {
+        let mut size = 1;
+        for mv_node in &self.children {
+            let node = mv_node.node.lock().unwrap();
+            if visited.insert(node.key()) {
+                // Recursively add the size of the child nodes
+                size += node.size_visited(visited);
+            }
+        }
+        size
+    }
+
+    /// Determine the height of the tree
+    pub fn height(&self) -> usize {
+        self.height_memo(&mut HashMap::new())
+    }
+
+    fn height_memo(&self, heights: &mut HashMap<NodeKey, usize>) -> usize {
+        if self.is_leaf() {
+            // A leaf node has height 0
+            0
+        } else {
+            // Determine the maximum height of its child nodes
+            let mut max_height = 0;
+            for child in &self.children {
+                let node = child.node.lock().unwrap();
+                let key = node.key();
+                let height = match heights.get(&key) {
+                    Some(&height) => height,
+                    None => {
+                        let height = node.height_memo(heights);
+                        heights.insert(key, height);
+                        height
+                    }
+                };
+                if height > max_height {
+                    max_height = height;
+                }
+            }
+            1 + max_height
+        }
+    }
+
+    /// Gets the best move, if available
+    pub fn best_move(&self) -> Option<&MCTreeMove> {
+        // Select the most promising move
+        MCTreeMove::max_play(&self.children)
+    }
+
+    /// Updates the current node with the given result
+    pub fn update(&mut self, result: &SimResult) {
+        self.playouts += result.playouts;
+        self.wins += result.wins;
+    }
+
+    /// Selects the next node to expand
+    pub fn select(
+        &mut self,
+        table: &mut TranspositionTable,
+        settings: &SearchSettings,
+    ) -> SimResult {
+        if let Some(proof) = self.proof {
+            // The result is already known, no need to search further
+            let result = SimResult::from_proof(proof, PARALLEL_PLAYOUTS);
+            self.update(&result);
+            result
+        } else if self.is_leaf() {
+            // Leaf nodes can be expanded
+            self.expand(table, settings)
+        } else {
+            // Select the most promising child node
+            let playouts = self.playouts;
+            let best_selection =
+                MCTreeMove::max_select_mut(&mut self.children, playouts, &*settings.selection)
+                    .unwrap();
+            // The child node has the opposite player, invert the result
+            let result = best_selection
+                .node
+                .lock()
+                .unwrap()
+                .select(table, settings)
+                .invert();
+            // Only the nodes on the selected path are updated,
+            // the other parents of a shared node keep their statistics
+            best_selection.playouts += result.playouts;
+            self.update(&result);
+            self.update_proof();
+            // Backtrack result
+            result
+        }
+    }
+
+    /// Expands and update the selected node
+    pub fn expand(
+        &mut self,
+        table: &mut TranspositionTable,
+        settings: &SearchSettings,
+    ) -> SimResult {
+        let play_result = PlayResult::get_result(&self.state, self.player());
+
+        // Generate child nodes if necessary
+        match play_result {
+            // There are still moves to make
+            PlayResult::Moves(moves) => {
+                // Generate child nodes, reusing transposed positions
+                // Only some selection policies look at the priors
+                let priors = if settings.selection.uses_priors() {
+                    settings.priors.priors(&self.state, &moves)
+                } else {
+                    UniformPriors::new().priors(&self.state, &moves)
+                };
+                for (mv, prior) in moves.iter().zip(priors) {
+                    let mut new_state = self.state.clone();
+                    new_state.apply_move(*mv);
+                    let node = table.get_or_insert(&new_state);
+                    self.children.push(MCTreeMove::new(*mv, prior, node));
+                }
+                // Perform simulations
+                let mut result = SimResult {
+                    wins: 0,
+                    playouts: 0,
+                };
+                let mut rng = rand::thread_rng();
+                for _ in 0..PARALLEL_SIMULATIONS {
+                    // Select a child node for simulation
+                    let rnd = rng.gen_range(0, self.children.len());
+                    let child = &mut self.children[rnd];
+                    // Make a simulation step
+                    let child_result = child.node.lock().unwrap().simulate(settings).invert();
+                    child.playouts += child_result.playouts;
+                    result += child_result;
+                }
+                self.update(&result);
+                result
+            }
+            // This node is the end of the game, its result is proven
+            PlayResult::End(_) => {
+                let proof = Proof::from_end(&self.state);
+                self.proof = Some(proof);
+                let result = SimResult::from_proof(proof, PARALLEL_PLAYOUTS);
+                self.update(&result);
+                result
+            }
+        }
+    }
+
+    /// Tries to prove this node from the proofs of its children
+    fn update_proof(&mut self) {
+        let mut win_plies: Option<usize> = None;
+        let mut loss_plies = 0;
+        let mut all_wins = true;
+        let mut all_proven = true;
+
+        for child in &self.children {
+            match child.node.lock().unwrap().proof {
+                // The opponent is lost, so this player wins
+                Option::Some(Proof::Loss(plies)) => {
+                    win_plies = Some(match win_plies {
+                        Some(win) if win <= plies + 1 => win,
+                        _ => plies + 1,
+                    });
+                }
+                // The opponent wins, delay it as long as possible
+                Option::Some(Proof::Win(plies)) => {
+                    if plies + 1 > loss_plies {
+                        loss_plies = plies + 1;
+                    }
+                }
+                Option::Some(Proof::Draw) => all_wins = false,
+                Option::None => {
+                    all_wins = false;
+                    all_proven = false;
+                }
+            }
+        }
+
+        self.proof = if let Some(plies) = win_plies {
+            // One winning move suffices
+            Some(Proof::Win(plies))
+        } else if all_wins {
+            // Every move loses
+            Some(Proof::Loss(loss_plies))
+        } else if all_proven {
+            // The best this player can get is a draw
+            Some(Proof::Draw)
+        } else {
+            None
+        };
+    }
+
+    /// Makes a simulation step for this move
+    pub fn simulate(&mut self, settings: &SearchSettings) -> SimResult {
+        let playouts = PARALLEL_PLAYOUTS;
+        let (tx, rx) = mpsc::channel();
+        // Perform playouts in parallel
+        for _ in 0..playouts {
+            let board = self.state.clone();
+            let tx = tx.clone();
+            let policy = Arc::clone(&settings.playouts);
+            let stats = Arc::clone(&settings.playout_stats);
+            let depth = settings.playout_depth;
+            thread::spawn(move || {
+                let result = MCTree::single_playout(board, &*policy, &stats, depth);
+                tx.send(result).unwrap();
+            });
+        }
+
+        let mut wins = 0;
+
+        // Aggregate results
+        for _ in 0..playouts {
+            let result = rx.recv().unwrap();
+            match result {
+                PlayEnd::Win => wins += 1,
+                PlayEnd::Loss => (),
+            }
+        }
+        let result = SimResult { playouts, wins };
+        self.update(&result);
+        result
+    }
+
+    /// Performs a singular playout
+    fn single_playout(
+        board: Board,
+        policy: &dyn PlayoutPolicy,
+        stats: &PlayoutStats,
+        depth: Option<usize>,
+    ) -> PlayEnd {
+        let mut board = board.clone();
+        let player = board.turn();
+        let mut rng = rand::thread_rng();
+        let mut plies = 0;
+        // Simulate
+        loop {
+            // Check for game end
+            let result = PlayResult::get_result(&board, player);
+
+            match result {
+                PlayResult::Moves(_) if depth.is_some_and(|depth| plies >= depth) => {
+                    // Cut the playout short and let the evaluation decide
+                    stats.record(policy, plies);
+                    return MCTree::evaluate_playout(&board, player, &mut rng);
+                }
+                PlayResult::Moves(moves) => {
+                    // Let the policy choose the move
+                    let mv = policy.choose_move(&board, &moves, &mut rng);
+                    // Playout with that move
+                    board.apply_move(mv);
+                    plies += 1;
+                }
+                PlayResult::End(end) => {
+                    // The game ended, return the results
+                    stats.record(policy, plies);
+                    return end;
+                }
+            }
+        }
+    }
+
+    /// Determines the result of an unfinished playout by a static evaluation
+    fn evaluate_playout(board: &Board, player: Player, rng: &mut ThreadRng) -> PlayEnd {
+        let score = match player {
+            Player::White => evaluate(board),
+            Player::Black => -evaluate(board),
+        };
+        // Win with the estimated probability
+        if rng.gen::<f32>() < win_probability(score) {
+            PlayEnd::Win
+        } else {
+            PlayEnd::Loss
+        }
+    }
+
+    /// Determines if the node is a leaf node.
+    pub fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    /// Determines how valuable it is to play this move.
+    pub fn play_value(&self) -> f32 {
+        if self.playouts == 0 {
+            0.5
+        } else {
+            // Determine 'winrate', but for the opponent
+            1. - (self.wins as f32) / (self.playouts as f32)
+        }
+    }
+
+    /// Determines the variance of the playout results of this node.
+    pub fn variance(&self) -> f32 {
+        let value = self.play_value();
+        // Each playout is either won or lost
+        value * (1. - value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mate_in_a_drawn_endgame_is_a_win() {
+        // Two knights can't force mate, but this is one
+        let board = Board::from_fen("7k/4NN2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(endgame::known_draw(&board));
+        assert!(matches!(
+            PlayResult::get_result(&board, Player::White),
+            PlayResult::End(PlayEnd::Win)
+        ));
+        assert!(matches!(
+            PlayResult::get_result(&board, Player::Black),
+            PlayResult::End(PlayEnd::Loss)
+        ));
+    }
+
+    /// The board after the moves, given like `e2e4`
+    fn board_after(fen: &str, moves: &[&str]) -> Board {
+        let mut board = Board::from_fen(fen).unwrap();
+        for mv in moves {
+            assert!(board.apply_uci_move(mv), "illegal move {}", mv);
+        }
+        board
+    }
+
+    const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    #[test]
+    fn transposed_positions_share_a_node() {
+        let mut table = TranspositionTable::new();
+        let first = table.get_or_insert(&board_after(START, &["g1f3", "g8f6", "b1c3"]));
+        let second = table.get_or_insert(&board_after(START, &["b1c3", "g8f6", "g1f3"]));
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(table.hits, 1);
+
+        // The same placement with another halfmove clock can end differently
+        let fresh =
+            table.get_or_insert(&Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 40").unwrap());
+        let old =
+            table.get_or_insert(&Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 49 40").unwrap());
+        assert!(!Arc::ptr_eq(&fresh, &old));
+        assert_eq!(table.hits, 1);
+
+        // Nodes are dropped with the last parent
+        drop((first, second, fresh));
+        table.prune();
+        assert_eq!(table.nodes.len(), 1);
+    }
+
+    /// Adds up the playouts of all moves into each node of the DAG
+    fn incoming_playouts(
+        node: &MCTree,
+        incoming: &mut HashMap<NodeKey, usize>,
+        seen: &mut HashSet<NodeKey>,
+    ) {
+        for child in &node.children {
+            let child_node = child.node.lock().unwrap();
+            *incoming.entry(child_node.key()).or_insert(0) += child.playouts;
+            if seen.insert(child_node.key()) {
+                incoming_playouts(&child_node, incoming, seen);
+            }
+        }
+    }
+
+    #[test]
+    fn searches_reuse_transposed_nodes() {
+        // Only the kings can move, so their moves transpose a lot
+        let board = Board::from_fen("4k3/8/8/p1p1p1p1/P1P1P1P1/8/8/4K3 w - - 0 1").unwrap();
+        let mut table = TranspositionTable::new();
+        let root = table.get_or_insert(&board);
+        let settings = SearchSettings {
+            playout_depth: Some(4),
+            ..SearchSettings::default()
+        };
+        for _ in 0..300 {
+            root.lock().unwrap().select(&mut table, &settings);
+        }
+
+        let root = root.lock().unwrap();
+        root.assert_valid();
+        assert!(table.hits > 0);
+
+        // Each node is reached through several moves, but counted once
+        let mut incoming = HashMap::new();
+        let mut seen = HashSet::new();
+        incoming_playouts(&root, &mut incoming, &mut seen);
+        assert_eq!(root.size(), seen.len() + 1);
+        // A shared node has at least the playouts of all the moves into it
+        for (key, playouts) in incoming.iter() {
+            let node = table.nodes[key].upgrade().unwrap();
+            assert!(node.lock().unwrap().playouts >= *playouts);
+        }
+    }
+}
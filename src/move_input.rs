@@ -0,0 +1,449 @@
+use super::san::{from_san, matching_moves, to_san};
+use pleco::core::CastleType;
+use pleco::{BitMove, Board, PieceType, SQ};
+
+/// The maximum number of legal moves suggested for a rejected input
+const MAX_SUGGESTIONS: usize = 8;
+
+/// Finds the legal move written in UCI like `e2e4` or in SAN like `Nf3`.
+///
+/// Explains why the input isn't a legal move otherwise, with similar legal moves.
+pub fn parse_move(board: &Board, input: &str) -> Result<BitMove, String> {
+    let input = input.trim();
+    let moves = board.generate_moves();
+    if let Some(mv) = moves.iter().find(|mv| mv.stringify() == input) {
+        return Ok(*mv);
+    }
+    if let Some(mv) = from_san(board, input) {
+        return Ok(mv);
+    }
+
+    let (reason, suggestions) = match parse_uci(input) {
+        Some((src, dest, promo)) => explain_uci(board, src, dest, promo),
+        None if looks_like_san(input) => explain_san(board, input),
+        None => (format!("'{}' is not a move.", input), Vec::new()),
+    };
+    if suggestions.is_empty() {
+        Err(format!("{} Enter moves like e2e4 or Nf3.", reason))
+    } else {
+        let suggestions: Vec<String> = suggestions
+            .iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|mv| format!("{} ({})", to_san(board, *mv), mv.stringify()))
+            .collect();
+        Err(format!(
+            "{}\nDid you mean {}?",
+            reason,
+            suggestions.join(", ")
+        ))
+    }
+}
+
+/// Splits a move like `e7e8q` into its squares and the promotion
+fn parse_uci(input: &str) -> Option<(SQ, SQ, Option<PieceType>)> {
+    if !input.is_ascii() || input.len() < 4 || input.len() > 5 {
+        return None;
+    }
+    let src = parse_square(&input[0..2])?;
+    let dest = parse_square(&input[2..4])?;
+    let promo = match input[4..].chars().next() {
+        None => None,
+        Some(c) => Some(promotion_type(c)?),
+    };
+    Some((src, dest, promo))
+}
+
+/// Parses a square like `e2`
+pub fn parse_square(name: &str) -> Option<SQ> {
+    (0..64).map(SQ).find(|sq| sq.to_string() == name)
+}
+
+fn promotion_type(c: char) -> Option<PieceType> {
+    match c {
+        'q' => Some(PieceType::Q),
+        'r' => Some(PieceType::R),
+        'b' => Some(PieceType::B),
+        'n' => Some(PieceType::N),
+        _ => None,
+    }
+}
+
+/// Checks if the input only has characters of standard algebraic notation and a square
+fn looks_like_san(input: &str) -> bool {
+    let castle = input
+        .trim_end_matches(['+', '#', '!', '?'])
+        .replace('0', "O");
+    if castle == "O-O" || castle == "O-O-O" {
+        return true;
+    }
+    let chars: Vec<char> = input.chars().collect();
+    chars
+        .iter()
+        .all(|c| "KQRBNabcdefgh12345678x=+#!?-".contains(*c))
+        && chars
+            .windows(2)
+            .any(|pair| ('a'..='h').contains(&pair[0]) && ('1'..='8').contains(&pair[1]))
+}
+
+fn piece_name(piece_type: PieceType) -> &'static str {
+    match piece_type {
+        PieceType::P => "pawn",
+        PieceType::N => "knight",
+        PieceType::B => "bishop",
+        PieceType::R => "rook",
+        PieceType::Q => "queen",
+        _ => "king",
+    }
+}
+
+/// Explains a move in UCI that isn't legal, with suggestions
+fn explain_uci(
+    board: &Board,
+    src: SQ,
+    dest: SQ,
+    promo: Option<PieceType>,
+) -> (String, Vec<BitMove>) {
+    let moves = board.generate_moves();
+    let from_src: Vec<BitMove> = moves
+        .iter()
+        .cloned()
+        .filter(|mv| mv.get_src() == src)
+        .collect();
+    let to_dest: Vec<BitMove> = moves
+        .iter()
+        .cloned()
+        .filter(|mv| mv.get_dest() == dest)
+        .collect();
+
+    let piece = board.piece_at_sq(src);
+    if piece.type_of() == PieceType::None {
+        return (format!("There is no piece on {}.", src), to_dest);
+    }
+    let name = piece_name(piece.type_of());
+    if piece.player_lossy() != board.turn() {
+        return (
+            format!(
+                "The {} on {} belongs to {}, but {} is to move.",
+                name,
+                src,
+                piece.player_lossy(),
+                board.turn()
+            ),
+            to_dest,
+        );
+    }
+
+    // A king moving two files wants to castle
+    let distance = dest.file_idx_of_sq() as i8 - src.file_idx_of_sq() as i8;
+    if piece.type_of() == PieceType::K
+        && src.rank_idx_of_sq() == dest.rank_idx_of_sq()
+        && (distance == 2 || distance == -2)
+    {
+        let castle = moves
+            .iter()
+            .cloned()
+            .filter(|mv| mv.is_castle() && mv.is_king_castle() == (distance > 0))
+            .collect();
+        return (castling_reason(board, distance > 0), castle);
+    }
+
+    // The squares fit, but not the promotion
+    let same_squares: Vec<BitMove> = from_src
+        .iter()
+        .cloned()
+        .filter(|mv| mv.get_dest() == dest)
+        .collect();
+    if let Some(mv) = same_squares.first() {
+        let reason = if mv.is_promo() {
+            format!(
+                "The pawn promotes on {}, add the piece like {}{}q.",
+                dest, src, dest
+            )
+        } else {
+            String::from("Only pawns that reach the last rank promote.")
+        };
+        return (reason, same_squares);
+    }
+
+    let pseudo_legal = piece_rule_moves(board)
+        .iter()
+        .any(|mv| mv.get_src() == src && mv.get_dest() == dest && promo_fits(*mv, promo));
+    let reason = if pseudo_legal {
+        king_safety_reason(board, src, dest)
+    } else if from_src.is_empty() {
+        format!("The {} on {} has no legal moves.", name, src)
+    } else {
+        format!("The {} on {} can't move to {}.", name, src, dest)
+    };
+    let suggestions = if from_src.is_empty() {
+        to_dest
+    } else {
+        from_src
+    };
+    (reason, suggestions)
+}
+
+/// The moves that follow the rules of the pieces, but may leave the own king in check
+fn piece_rule_moves(board: &Board) -> Vec<BitMove> {
+    let mut moves: Vec<BitMove> = board.generate_pseudolegal_moves().to_vec();
+    if !board.in_check() {
+        return moves;
+    }
+
+    // In check, only the moves that may stop it are generated, so add the moves of the pieces
+    let player = board.turn();
+    let own = board.get_occupied_player(player);
+    let opponent = board.get_occupied_player(!player);
+    for &piece_type in &[
+        PieceType::N,
+        PieceType::B,
+        PieceType::R,
+        PieceType::Q,
+        PieceType::K,
+    ] {
+        for src in board.piece_bb(player, piece_type) {
+            for dest in board.attacks_from(piece_type, src, player) & !own {
+                let known = moves
+                    .iter()
+                    .any(|mv| mv.get_src() == src && mv.get_dest() == dest);
+                if known {
+                    continue;
+                }
+                moves.push(if (opponent & dest.to_bb()).is_not_empty() {
+                    BitMove::make_capture(src, dest)
+                } else {
+                    BitMove::make_quiet(src, dest)
+                });
+            }
+        }
+    }
+    moves
+}
+
+fn promo_fits(mv: BitMove, promo: Option<PieceType>) -> bool {
+    match promo {
+        Some(piece_type) => mv.is_promo() && mv.promo_piece() == piece_type,
+        None => !mv.is_promo(),
+    }
+}
+
+/// Explains a move that follows the piece rules but leaves the own king in check
+fn king_safety_reason(board: &Board, src: SQ, dest: SQ) -> String {
+    let piece_type = board.piece_at_sq(src).type_of();
+    if piece_type == PieceType::K {
+        format!("The king would be in check on {}.", dest)
+    } else if board.in_check() {
+        String::from("Your king is in check, and this move doesn't stop it.")
+    } else {
+        format!(
+            "The {} on {} is pinned, moving it would put your king in check.",
+            piece_name(piece_type),
+            src
+        )
+    }
+}
+
+/// Explains why the player to move can't castle to the side
+fn castling_reason(board: &Board, king_side: bool) -> String {
+    let side = if king_side {
+        CastleType::KingSide
+    } else {
+        CastleType::QueenSide
+    };
+    let name = if king_side { "kingside" } else { "queenside" };
+    if !board.can_castle(board.turn(), side) {
+        format!("{} can't castle {} anymore.", board.turn(), name)
+    } else if board.castle_impeded(side) {
+        format!(
+            "There are pieces between the king and the rook on the {}.",
+            name
+        )
+    } else if board.in_check() {
+        String::from("The king can't castle out of check.")
+    } else {
+        String::from("The king can't castle through or into an attacked square.")
+    }
+}
+
+/// Explains a move in standard algebraic notation that isn't legal, with suggestions
+fn explain_san(board: &Board, input: &str) -> (String, Vec<BitMove>) {
+    let moves = board.generate_moves();
+    let candidates = matching_moves(board, input, &moves);
+    if candidates.len() > 1 {
+        return (
+            format!(
+                "{} is ambiguous, name the file or rank of the piece.",
+                input
+            ),
+            candidates,
+        );
+    }
+
+    let castle = input
+        .trim_end_matches(['+', '#', '!', '?'])
+        .replace('0', "O");
+    if castle == "O-O" || castle == "O-O-O" {
+        return (castling_reason(board, castle == "O-O"), Vec::new());
+    }
+
+    // A pawn reaching the last rank without the piece it becomes
+    let promotions = matching_moves(board, &format!("{}=Q", input), &moves);
+    if !promotions.is_empty() {
+        return (
+            format!("The pawn promotes, add the piece like {}=Q.", input),
+            promotions,
+        );
+    }
+
+    if let Some(mv) = matching_moves(board, input, &piece_rule_moves(board)).first() {
+        let from_src = moves
+            .iter()
+            .cloned()
+            .filter(|other| other.get_src() == mv.get_src())
+            .collect();
+        return (
+            king_safety_reason(board, mv.get_src(), mv.get_dest()),
+            from_src,
+        );
+    }
+
+    // Moves of the same piece type, or of pawns on the same file
+    let first = input.chars().next();
+    let similar: Vec<BitMove> = moves
+        .iter()
+        .cloned()
+        .filter(|mv| to_san(board, *mv).chars().next() == first)
+        .collect();
+    (format!("{} isn't possible here.", input), similar)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    /// The message of a rejected input
+    fn rejection(fen: &str, input: &str) -> String {
+        let board = Board::from_fen(fen).unwrap();
+        match parse_move(&board, input) {
+            Ok(mv) => panic!("{} was accepted as {}", input, mv),
+            Err(message) => message,
+        }
+    }
+
+    /// The first line of a rejection, without the suggestions or the hint
+    fn reason(fen: &str, input: &str) -> String {
+        let message = rejection(fen, input);
+        let reason = message.lines().next().unwrap();
+        reason
+            .trim_end_matches(" Enter moves like e2e4 or Nf3.")
+            .to_string()
+    }
+
+    #[test]
+    fn legal_moves_are_accepted_in_both_notations() {
+        let board = Board::from_fen(START).unwrap();
+        for input in &["e2e4", "e4", " e4 ", "g1f3", "Nf3"] {
+            assert!(parse_move(&board, input).is_ok(), "{}", input);
+        }
+        assert_eq!(parse_move(&board, "Nf3"), parse_move(&board, "g1f3"));
+    }
+
+    #[test]
+    fn malformed_inputs_arent_moves() {
+        for input in &["", "hello", "e9e4", "e2e4k", "Pe4"] {
+            assert_eq!(
+                rejection(START, input),
+                format!("'{}' is not a move. Enter moves like e2e4 or Nf3.", input)
+            );
+        }
+    }
+
+    #[test]
+    fn ambiguous_moves_name_the_candidates() {
+        // Both knights can reach d2
+        let fen = "rnbqkb1r/ppp1pppp/5n2/3p4/3P4/5N2/PPP1PPPP/RNBQKB1R w KQkq - 2 3";
+        assert_eq!(
+            rejection(fen, "Nd2"),
+            "Nd2 is ambiguous, name the file or rank of the piece.\n\
+             Did you mean Nbd2 (b1d2), Nfd2 (f3d2)?"
+        );
+        assert!(parse_move(&Board::from_fen(fen).unwrap(), "Nbd2").is_ok());
+    }
+
+    #[test]
+    fn illegal_moves_explain_the_rule() {
+        assert_eq!(reason(START, "e3e4"), "There is no piece on e3.");
+        assert_eq!(
+            reason(START, "e7e5"),
+            "The pawn on e7 belongs to Black, but White is to move."
+        );
+        assert_eq!(reason(START, "e2e5"), "The pawn on e2 can't move to e5.");
+        assert_eq!(
+            reason(START, "c1e3"),
+            "The bishop on c1 has no legal moves."
+        );
+        assert_eq!(reason(START, "Ke2"), "Ke2 isn't possible here.");
+        assert_eq!(
+            reason(START, "g1f3q"),
+            "Only pawns that reach the last rank promote."
+        );
+
+        // Pinned pieces, moves ignoring a check and kings walking into check
+        let pinned = "4k3/8/8/8/1b6/8/3N4/4K3 w - - 0 1";
+        assert_eq!(
+            reason(pinned, "d2f3"),
+            "The knight on d2 is pinned, moving it would put your king in check."
+        );
+        assert_eq!(reason(pinned, "Nf3"), reason(pinned, "d2f3"));
+        let check = "4k3/8/8/8/8/8/3N4/r3K3 w - - 0 1";
+        assert_eq!(
+            reason(check, "d2f3"),
+            "Your king is in check, and this move doesn't stop it."
+        );
+        // The knight blocks the check, but it is pinned as well
+        let pinned_check = "4k3/8/8/8/1b6/8/3N4/r3K3 w - - 0 1";
+        assert_eq!(
+            reason(pinned_check, "d2b1"),
+            "Your king is in check, and this move doesn't stop it."
+        );
+        assert_eq!(reason(check, "e1d1"), "The king would be in check on d1.");
+        assert_eq!(reason(check, "Kd1"), reason(check, "e1d1"));
+    }
+
+    #[test]
+    fn promotions_need_the_piece() {
+        let fen = "8/4P3/8/8/8/8/k7/4K3 w - - 0 1";
+        assert_eq!(
+            reason(fen, "e7e8"),
+            "The pawn promotes on e8, add the piece like e7e8q."
+        );
+        assert_eq!(
+            reason(fen, "e8"),
+            "The pawn promotes, add the piece like e8=Q."
+        );
+        assert!(parse_move(&Board::from_fen(fen).unwrap(), "e7e8n").is_ok());
+    }
+
+    #[test]
+    fn castling_explains_what_stops_it() {
+        assert_eq!(
+            reason(START, "e1g1"),
+            "There are pieces between the king and the rook on the kingside."
+        );
+        assert_eq!(reason(START, "O-O-O"), reason(START, "0-0-0"));
+        let moved = "r3k2r/8/8/8/8/8/8/R3K2R w kq - 0 1";
+        assert_eq!(reason(moved, "O-O"), "White can't castle kingside anymore.");
+        let attacked = "r3k2r/8/8/8/8/8/5r2/R3K2R w KQkq - 0 1";
+        assert_eq!(
+            reason(attacked, "e1g1"),
+            "The king can't castle through or into an attacked square."
+        );
+        let check = "r3k2r/8/8/8/8/8/4r3/R3K2R w KQkq - 0 1";
+        assert_eq!(
+            reason(check, "O-O-O"),
+            "The king can't castle out of check."
+        );
+    }
+}
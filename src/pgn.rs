@@ -0,0 +1,433 @@
+use super::san::{from_san, to_san};
+use pleco::{BitMove, Board, Player};
+
+use std::fmt;
+use std::time::SystemTime;
+
+/// The maximum length of a line of move text
+const LINE_WIDTH: usize = 80;
+
+/// The outcome of a finished game
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+impl GameResult {
+    /// The result as written in PGN
+    pub fn pgn_str(self) -> &'static str {
+        match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+        }
+    }
+
+    /// The points the given player scored
+    pub fn score(self, player: Player) -> f32 {
+        match (self, player) {
+            (GameResult::Draw, _) => 0.5,
+            (GameResult::WhiteWins, Player::White) | (GameResult::BlackWins, Player::Black) => 1.,
+            _ => 0.,
+        }
+    }
+}
+
+/// A game that can be written as PGN
+pub struct PgnGame {
+    pub event: String,
+    pub round: String,
+    pub white: String,
+    pub black: String,
+    /// The position the game started from
+    pub start: Board,
+    pub moves: Vec<BitMove>,
+    /// The result, if the game is finished
+    pub result: Option<GameResult>,
+    /// Why the game ended, like `checkmate` or `threefold repetition`
+    pub termination: Option<String>,
+    /// Any other tags, like `WhiteElo`
+    pub tags: Vec<(String, String)>,
+}
+
+impl PgnGame {
+    /// Gets the value of one of the other tags
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Writes the moves in standard algebraic notation, with move numbers
+    fn move_text(&self) -> String {
+        let mut board = self.start.clone();
+        let mut tokens = Vec::new();
+        for (index, mv) in self.moves.iter().enumerate() {
+            let number = board.moves_played() / 2 + 1;
+            match board.turn() {
+                Player::White => tokens.push(format!("{}.", number)),
+                // The game might start with a move of black
+                Player::Black if index == 0 => tokens.push(format!("{}...", number)),
+                Player::Black => (),
+            }
+            tokens.push(to_san(&board, *mv));
+            board.apply_move(*mv);
+        }
+        tokens.push(
+            self.result
+                .map(GameResult::pgn_str)
+                .unwrap_or("*")
+                .to_string(),
+        );
+
+        // Wrap the text into lines
+        let mut text = String::new();
+        let mut line_len = 0;
+        for token in tokens {
+            if line_len > 0 && line_len + 1 + token.len() > LINE_WIDTH {
+                text.push('\n');
+                line_len = 0;
+            } else if line_len > 0 {
+                text.push(' ');
+                line_len += 1;
+            }
+            line_len += token.len();
+            text += &token;
+        }
+        text
+    }
+}
+
+impl fmt::Display for PgnGame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let result = self.result.map(GameResult::pgn_str).unwrap_or("*");
+        writeln!(f, "[Event \"{}\"]", self.event)?;
+        writeln!(f, "[Site \"cli-chess\"]")?;
+        writeln!(f, "[Date \"{}\"]", pgn_date(SystemTime::now()))?;
+        writeln!(f, "[Round \"{}\"]", self.round)?;
+        writeln!(f, "[White \"{}\"]", self.white)?;
+        writeln!(f, "[Black \"{}\"]", self.black)?;
+        writeln!(f, "[Result \"{}\"]", result)?;
+        if self.start.fen() != Board::start_pos().fen() {
+            writeln!(f, "[SetUp \"1\"]")?;
+            writeln!(f, "[FEN \"{}\"]", self.start.fen())?;
+        }
+        if let Some(termination) = &self.termination {
+            writeln!(f, "[Termination \"{}\"]", termination)?;
+        }
+        for (name, value) in self.tags.iter() {
+            writeln!(f, "[{} \"{}\"]", name, value)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "{}", self.move_text())
+    }
+}
+
+/// Reads all games of a PGN file, games that can't be read are returned as errors
+pub fn parse_games(text: &str) -> Vec<Result<PgnGame, String>> {
+    let mut games = Vec::new();
+    let mut tags: Vec<(String, String)> = Vec::new();
+    let mut move_text = String::new();
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('[') && !in_comment(&move_text) {
+            if !move_text.trim().is_empty() {
+                // The tags of the next game
+                games.push(parse_game(&tags, &move_text));
+                tags.clear();
+                move_text.clear();
+            }
+            if let Some(tag) = parse_tag(line) {
+                tags.push(tag);
+            }
+        } else if !line.starts_with('%') {
+            move_text += line;
+            move_text.push('\n');
+        }
+    }
+    if !tags.is_empty() || !move_text.trim().is_empty() {
+        games.push(parse_game(&tags, &move_text));
+    }
+    games
+}
+
+/// Checks if the move text ends inside a comment
+fn in_comment(move_text: &str) -> bool {
+    move_text.matches('{').count() > move_text.matches('}').count()
+}
+
+/// Parses a tag like `[White "Tal, Mikhail"]`
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?;
+    let mut parts = inner.splitn(2, char::is_whitespace);
+    let name = parts.next()?.to_string();
+    let value = parts.next()?.trim().trim_matches('"').replace("\\\"", "\"");
+    Some((name, value))
+}
+
+/// Replays the move text of a game
+fn parse_game(tags: &[(String, String)], move_text: &str) -> Result<PgnGame, String> {
+    let tag = |name: &str| {
+        tags.iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.clone())
+    };
+    let start = match tag("FEN") {
+        Some(fen) => Board::from_fen(&fen).map_err(|_| format!("Invalid FEN '{}'.", fen))?,
+        None => Board::start_pos(),
+    };
+
+    let mut board = start.clone();
+    let mut moves = Vec::new();
+    let mut result = None;
+    for token in move_tokens(move_text) {
+        match token.as_str() {
+            "1-0" => result = Some(GameResult::WhiteWins),
+            "0-1" => result = Some(GameResult::BlackWins),
+            "1/2-1/2" => result = Some(GameResult::Draw),
+            "*" => (),
+            // Skip move numbers, like 12. or 12...
+            token => match token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.') {
+                "" => (),
+                san => {
+                    let mv = from_san(&board, san).ok_or(format!(
+                        "Illegal move '{}' after {} moves of '{} vs {}'.",
+                        san,
+                        moves.len(),
+                        tag("White").unwrap_or_default(),
+                        tag("Black").unwrap_or_default()
+                    ))?;
+                    board.apply_move(mv);
+                    moves.push(mv);
+                }
+            },
+        }
+    }
+
+    let seven_tag_roster = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+    let other_tags = ["SetUp", "FEN", "Termination"];
+    Ok(PgnGame {
+        event: tag("Event").unwrap_or_default(),
+        round: tag("Round").unwrap_or_default(),
+        white: tag("White").unwrap_or_default(),
+        black: tag("Black").unwrap_or_default(),
+        start,
+        moves,
+        result,
+        termination: tag("Termination"),
+        tags: tags
+            .iter()
+            .filter(|(name, _)| {
+                !seven_tag_roster.contains(&name.as_str()) && !other_tags.contains(&name.as_str())
+            })
+            .cloned()
+            .collect(),
+    })
+}
+
+/// Splits the move text into tokens, without comments, variations and annotation glyphs
+fn move_tokens(move_text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut chars = move_text.chars();
+    let mut variation_depth = 0;
+
+    while let Some(c) = chars.next() {
+        let separator = match c {
+            '{' => {
+                // Comments end at the next brace
+                chars.by_ref().find(|c| *c == '}');
+                true
+            }
+            ';' => {
+                // Comments end at the end of the line
+                chars.by_ref().find(|c| *c == '\n');
+                true
+            }
+            '(' => {
+                variation_depth += 1;
+                true
+            }
+            ')' => {
+                variation_depth -= 1;
+                true
+            }
+            c => c.is_whitespace(),
+        };
+        if separator {
+            if !token.is_empty() {
+                tokens.push(token.clone());
+                token.clear();
+            }
+        } else if variation_depth == 0 {
+            token.push(c);
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    // Numeric annotation glyphs, like $1
+    tokens.retain(|token| !token.starts_with('$'));
+    tokens
+}
+
+/// Formats the date of the given time like `2020.04.19`
+fn pgn_date(time: SystemTime) -> String {
+    let days = match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(since_epoch) => (since_epoch.as_secs() / 86_400) as i64,
+        Err(_) => return String::from("????.??.??"),
+    };
+
+    // Convert the days since the epoch into a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Plays the moves, given like `e2e4`, from the position
+    fn game(fen: &str, moves: &[&str], result: Option<GameResult>) -> PgnGame {
+        let start = Board::from_fen(fen).unwrap();
+        let mut board = start.clone();
+        let moves = moves
+            .iter()
+            .map(|uci| {
+                let mv = board
+                    .generate_moves()
+                    .iter()
+                    .cloned()
+                    .find(|mv| mv.stringify() == *uci)
+                    .unwrap();
+                board.apply_move(mv);
+                mv
+            })
+            .collect();
+        PgnGame {
+            event: String::from("Test"),
+            round: String::from("1"),
+            white: String::from("stonefish"),
+            black: String::from("random"),
+            start,
+            moves,
+            result,
+            termination: None,
+            tags: vec![(String::from("WhiteElo"), String::from("1500"))],
+        }
+    }
+
+    #[test]
+    fn games_are_written_with_the_seven_tag_roster() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let mut written = game(
+            start,
+            &["e2e4", "e7e5", "g1f3"],
+            Some(GameResult::BlackWins),
+        );
+        written.termination = Some(String::from("adjudication"));
+        let text = written.to_string();
+        for tag in [
+            "[Event \"Test\"]",
+            "[Round \"1\"]",
+            "[White \"stonefish\"]",
+            "[Black \"random\"]",
+            "[Result \"0-1\"]",
+            "[Termination \"adjudication\"]",
+        ]
+        .iter()
+        {
+            assert!(text.contains(tag), "{} is missing", tag);
+        }
+        assert!(!text.contains("[FEN"));
+        assert!(text.ends_with("\n\n1. e4 e5 2. Nf3 0-1\n"));
+    }
+
+    #[test]
+    fn games_from_a_position_keep_it() {
+        let fen = "4k3/1P6/8/8/8/8/8/4K2r b - - 0 40";
+        let text = game(fen, &["h1h2", "b7b8q", "e8d7"], None).to_string();
+        assert!(text.contains("[SetUp \"1\"]"));
+        assert!(text.contains(&format!("[FEN \"{}\"]", fen)));
+        assert!(text.contains("40... Rh2 41. b8=Q+ Kd7 *"));
+    }
+
+    #[test]
+    fn long_move_texts_are_wrapped() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let knights = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        let moves: Vec<&str> = knights.iter().cycle().take(60).cloned().collect();
+        let written = game(start, &moves, Some(GameResult::Draw));
+        let move_text = written.move_text();
+        assert!(move_text.lines().count() > 1);
+        assert!(move_text.lines().all(|line| line.len() <= LINE_WIDTH));
+        assert!(move_text.ends_with("1/2-1/2"));
+    }
+
+    #[test]
+    fn written_games_are_read_back() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let moves = [
+            "e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "g8f6", "e1g1", "f6e4", "f1e1", "d7d5", "c4d5",
+            "d8d5", "b1c3", "d5a5", "c3e4", "c8e6", "e4g5", "e8c8",
+        ];
+        let written = game(start, &moves, Some(GameResult::BlackWins));
+        let read = parse_games(&written.to_string()).remove(0).unwrap();
+        assert_eq!(read.moves, written.moves);
+        assert_eq!(read.result, Some(GameResult::BlackWins));
+        assert_eq!(read.white, "stonefish");
+        assert_eq!(read.black, "random");
+        assert_eq!(read.tag("WhiteElo"), Some("1500"));
+
+        let fen = "4k3/1P6/8/8/8/8/8/4K2r b - - 0 40";
+        let written = game(fen, &["h1h2", "b7b8q", "e8d7"], None);
+        let read = parse_games(&written.to_string()).remove(0).unwrap();
+        assert_eq!(read.start.fen(), written.start.fen());
+        assert_eq!(read.moves, written.moves);
+        assert_eq!(read.result, None);
+    }
+
+    #[test]
+    fn comments_variations_and_glyphs_are_skipped() {
+        let text = "[White \"a\"]\n[Black \"b\"]\n\n\
+                    1. e4 {best by test} e5 (1... c5 2. Nf3 {Sicilian}) 2. Nf3 $1 ; a comment\n\
+                    Nc6 1/2-1/2\n\n\
+                    [White \"c\"]\n\n1. d4 Nf6 2. Bg5 0-1\n";
+        let games = parse_games(text);
+        assert_eq!(games.len(), 2);
+        let first = games[0].as_ref().unwrap();
+        assert_eq!(first.moves.len(), 4);
+        assert_eq!(first.result, Some(GameResult::Draw));
+        let second = games[1].as_ref().unwrap();
+        assert_eq!(second.white, "c");
+        assert_eq!(second.moves.len(), 3);
+    }
+
+    #[test]
+    fn illegal_moves_are_errors() {
+        let games = parse_games("1. e4 e5 2. Ke3 *\n");
+        assert!(games[0].is_err());
+    }
+
+    #[test]
+    fn dates_are_civil_dates() {
+        assert_eq!(pgn_date(SystemTime::UNIX_EPOCH), "1970.01.01");
+        let leap_day = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(951_782_400);
+        assert_eq!(pgn_date(leap_day), "2000.02.29");
+    }
+}
@@ -0,0 +1,210 @@
+use super::chess_player::{
+    AlphaBetaPlayer, BookPlayer, ChessPlayer, HumanPlayer, PlecoBot, PlecoBotPlayer, RandomPlayer,
+    StoneFish,
+};
+use super::mcts::SearchSettings;
+use super::polyglot::{Book, BookSelection, PolyglotKeys};
+use super::{playout, priors, selection};
+use pleco::{Board, Player};
+use std::sync::Arc;
+
+/// Gets the value of the given command line option
+pub fn option_value<'a>(args: &'a [String], option: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == option)
+        .map(|index| args.get(index + 1).map(String::as_str).unwrap_or(""))
+}
+
+/// A player description like `stonefish,selection=uct:0.7,playouts=capture`.
+///
+/// Options missing in the spec fall back to the command line options of the same name,
+/// e.g. `--selection uct:0.7`.
+pub struct PlayerSpec<'a> {
+    /// The name of the player, like `stonefish` or `pleco-alphabeta:4`
+    pub name: &'a str,
+    options: Vec<(&'a str, &'a str)>,
+    args: &'a [String],
+}
+
+impl<'a> PlayerSpec<'a> {
+    pub fn parse(spec: &'a str, args: &'a [String]) -> Result<PlayerSpec<'a>, String> {
+        let mut parts = spec.split(',');
+        let name = parts.next().unwrap_or("");
+        let options = parts
+            .map(|option| {
+                let mut key_value = option.splitn(2, '=');
+                match (key_value.next(), key_value.next()) {
+                    (Some(key), Some(value)) => Ok((key, value)),
+                    _ => Err(format!(
+                        "Invalid option '{}' of player '{}'.\nUse <key>=<value>.",
+                        option, spec
+                    )),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(PlayerSpec {
+            name,
+            options,
+            args,
+        })
+    }
+
+    /// Gets the value of the option, from the spec or the command line
+    fn option(&self, key: &str) -> Option<&'a str> {
+        self.options
+            .iter()
+            .find(|(option, _)| *option == key)
+            .map(|(_, value)| *value)
+            .or_else(|| option_value(self.args, &format!("--{}", key)))
+    }
+
+    /// Determines the search settings of StoneFish
+    fn search_settings(&self) -> Result<SearchSettings, String> {
+        let mut settings = SearchSettings::default();
+
+        if let Some(spec) = self.option("selection") {
+            settings.selection = selection::parse_policy(spec).ok_or(format!(
+                "Unknown selection policy '{}'.\nUse uct[:c], ucb1-tuned, puct[:c] or bias[:w] with a positive weight.",
+                spec
+            ))?;
+        }
+        if let Some(spec) = self.option("priors") {
+            settings.priors = priors::parse_priors(spec).ok_or(format!(
+                "Unknown prior policy '{}'.\nUse uniform or heuristic[:temperature] with a positive temperature.",
+                spec
+            ))?;
+        }
+
+        if let Some(spec) = self.option("playouts") {
+            settings.playouts = Arc::from(playout::parse_playouts(spec).ok_or(format!(
+                "Unknown playout policy '{}'.\nUse random, capture, safe, greedy[:epsilon] or mate+<policy>, with an epsilon from 0 to 1.",
+                spec
+            ))?);
+        }
+
+        if let Some(depth) = self.option("playout-depth") {
+            let depth = depth.parse::<usize>().map_err(|_| {
+                format!(
+                    "Invalid playout depth '{}'.\nUse a number of moves, or 0 for no limit.",
+                    depth
+                )
+            })?;
+            settings.playout_depth = if depth == 0 { None } else { Some(depth) };
+        }
+
+        Ok(settings)
+    }
+
+    /// Creates the described player, with an opening book if one is given
+    pub fn create(
+        &self,
+        player: Player,
+        board: &Board,
+    ) -> Result<Box<dyn ChessPlayer + Send>, String> {
+        let inner = self.create_inner(player, board)?;
+        match self.option("book") {
+            // Humans choose their openings themselves
+            Some(path) if self.name != "human" => {
+                let keys = match self.option("book-keys") {
+                    Some(keys_path) => PolyglotKeys::load(keys_path)?,
+                    None => PolyglotKeys::standard(),
+                };
+                let selection = match self.option("book-select") {
+                    Some(name) => BookSelection::from_name(name).ok_or(format!(
+                        "Unknown book selection '{}'.\nUse weighted or best.",
+                        name
+                    ))?,
+                    None => BookSelection::Weighted,
+                };
+                let max_depth = match self.option("book-depth") {
+                    Some(depth) => Some(depth.parse::<u16>().map_err(|_| {
+                        format!(
+                            "Invalid book depth '{}'.\nUse a number of half moves.",
+                            depth
+                        )
+                    })?),
+                    None => None,
+                };
+                Ok(Box::new(BookPlayer::new(
+                    Book::load(path, keys)?,
+                    inner,
+                    selection,
+                    max_depth,
+                )))
+            }
+            _ => Ok(inner),
+        }
+    }
+
+    fn create_inner(
+        &self,
+        player: Player,
+        board: &Board,
+    ) -> Result<Box<dyn ChessPlayer + Send>, String> {
+        match self.name {
+            "human" => Ok(Box::new(HumanPlayer::new())),
+            "random" => Ok(Box::new(RandomPlayer::new())),
+            "stonefish" => Ok(Box::new(StoneFish::new(
+                player,
+                board,
+                self.search_settings()?,
+            ))),
+            "alphabeta" => {
+                let depth = match self.option("depth") {
+                    Some(depth) => match depth.parse::<u8>() {
+                        Ok(depth) if depth > 0 => Some(depth),
+                        _ => {
+                            return Err(format!(
+                                "Invalid search depth '{}'.\nUse a number of at least 1.",
+                                depth
+                            ))
+                        }
+                    },
+                    None => None,
+                };
+                Ok(Box::new(AlphaBetaPlayer::new(depth)))
+            }
+            _ => {
+                // Pleco bots, like pleco-alphabeta:4
+                let err = format!(
+                    "Unknown player '{}'.\nUse human, random, stonefish, alphabeta or pleco-<bot>[:depth].\n\
+                     The pleco bots are random, minimax, parallel-minimax, alphabeta, jamboree and iterative.",
+                    self.name
+                );
+                let spec = self
+                    .name
+                    .strip_prefix("pleco-")
+                    .ok_or_else(|| err.clone())?;
+                let mut parts = spec.splitn(2, ':');
+                let bot = parts
+                    .next()
+                    .and_then(PlecoBot::from_name)
+                    .ok_or_else(|| err.clone())?;
+                let depth = match parts.next() {
+                    Some(depth) => match depth.parse::<u16>() {
+                        Ok(depth) if depth > 0 => depth,
+                        _ => {
+                            return Err(format!(
+                                "Invalid search depth '{}' of player '{}'.\n\
+                                 Use a number of at least 1.",
+                                depth, self.name
+                            ))
+                        }
+                    },
+                    None => 3,
+                };
+                Ok(Box::new(PlecoBotPlayer::new(bot, depth)))
+            }
+        }
+    }
+}
+
+/// Creates the player described by the spec
+pub fn create_player(
+    spec: &str,
+    player: Player,
+    board: &Board,
+    args: &[String],
+) -> Result<Box<dyn ChessPlayer + Send>, String> {
+    PlayerSpec::parse(spec, args)?.create(player, board)
+}
@@ -0,0 +1,354 @@
+use super::eval::evaluate;
+use pleco::{BitMove, Board, MoveList, Player};
+use rand::{rngs::ThreadRng, Rng};
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// A strategy to choose the moves of a playout
+pub trait PlayoutPolicy: Send + Sync {
+    /// The name of the policy
+    fn name(&self) -> String;
+
+    /// Chooses the next move of a playout from the legal moves
+    fn choose_move(&self, board: &Board, moves: &MoveList, rng: &mut ThreadRng) -> BitMove;
+}
+
+/// Chooses a random move
+fn random_move(moves: &[BitMove], rng: &mut ThreadRng) -> BitMove {
+    moves[rng.gen_range(0, moves.len())]
+}
+
+/// Plays uniformly random moves
+pub struct RandomPlayouts {}
+
+impl RandomPlayouts {
+    pub fn new() -> RandomPlayouts {
+        RandomPlayouts {}
+    }
+}
+
+impl PlayoutPolicy for RandomPlayouts {
+    fn name(&self) -> String {
+        String::from("random")
+    }
+
+    fn choose_move(&self, _board: &Board, moves: &MoveList, rng: &mut ThreadRng) -> BitMove {
+        random_move(moves, rng)
+    }
+}
+
+/// Plays a random capture if possible, else a random move
+pub struct CaptureFirst {}
+
+impl CaptureFirst {
+    pub fn new() -> CaptureFirst {
+        CaptureFirst {}
+    }
+}
+
+impl PlayoutPolicy for CaptureFirst {
+    fn name(&self) -> String {
+        String::from("capture")
+    }
+
+    fn choose_move(&self, _board: &Board, moves: &MoveList, rng: &mut ThreadRng) -> BitMove {
+        let captures: Vec<BitMove> = moves.iter().filter(|mv| mv.is_capture()).cloned().collect();
+        if captures.is_empty() {
+            random_move(moves, rng)
+        } else {
+            random_move(&captures, rng)
+        }
+    }
+}
+
+/// Plays a random move that doesn't lose the exchange, if possible
+pub struct AvoidBlunders {}
+
+impl AvoidBlunders {
+    pub fn new() -> AvoidBlunders {
+        AvoidBlunders {}
+    }
+}
+
+impl PlayoutPolicy for AvoidBlunders {
+    fn name(&self) -> String {
+        String::from("safe")
+    }
+
+    fn choose_move(&self, board: &Board, moves: &MoveList, rng: &mut ThreadRng) -> BitMove {
+        let safe_moves: Vec<BitMove> = moves
+            .iter()
+            .filter(|mv| board.see_ge(**mv, 0))
+            .cloned()
+            .collect();
+        if safe_moves.is_empty() {
+            random_move(moves, rng)
+        } else {
+            random_move(&safe_moves, rng)
+        }
+    }
+}
+
+/// Plays the move with the best static evaluation, or a random move with a chance of epsilon
+pub struct EpsilonGreedy {
+    /// The chance to play a random move
+    pub epsilon: f32,
+}
+
+impl EpsilonGreedy {
+    pub fn new(epsilon: f32) -> EpsilonGreedy {
+        EpsilonGreedy { epsilon }
+    }
+}
+
+impl PlayoutPolicy for EpsilonGreedy {
+    fn name(&self) -> String {
+        format!("greedy:{}", self.epsilon)
+    }
+
+    fn choose_move(&self, board: &Board, moves: &MoveList, rng: &mut ThreadRng) -> BitMove {
+        if rng.gen::<f32>() < self.epsilon {
+            return random_move(moves, rng);
+        }
+
+        let sign = match board.turn() {
+            Player::White => 1,
+            Player::Black => -1,
+        };
+        let mut board = board.clone();
+        let mut best_moves = Vec::new();
+        let mut best_score = i32::MIN;
+        for mv in moves.iter() {
+            board.apply_move(*mv);
+            let score = sign * evaluate(&board);
+            board.undo_move();
+
+            if score > best_score {
+                best_score = score;
+                best_moves.clear();
+            }
+            if score == best_score {
+                best_moves.push(*mv);
+            }
+        }
+        // Break ties randomly
+        random_move(&best_moves, rng)
+    }
+}
+
+/// Plays a checkmate if there is one, else asks the inner policy
+pub struct MateInOne {
+    /// The policy for all other positions
+    pub inner: Box<dyn PlayoutPolicy>,
+}
+
+impl MateInOne {
+    pub fn new(inner: Box<dyn PlayoutPolicy>) -> MateInOne {
+        MateInOne { inner }
+    }
+}
+
+impl PlayoutPolicy for MateInOne {
+    fn name(&self) -> String {
+        format!("mate+{}", self.inner.name())
+    }
+
+    fn choose_move(&self, board: &Board, moves: &MoveList, rng: &mut ThreadRng) -> BitMove {
+        let mut mate_board = board.clone();
+        for mv in moves.iter() {
+            // Only checks can be checkmate
+            if board.gives_check(*mv) {
+                mate_board.apply_move(*mv);
+                let is_mate = mate_board.checkmate();
+                mate_board.undo_move();
+                if is_mate {
+                    return *mv;
+                }
+            }
+        }
+        self.inner.choose_move(board, moves, rng)
+    }
+}
+
+/// The number of playouts of a policy and their moves
+#[derive(Clone, Copy, Default)]
+struct PolicyStats {
+    /// The number of finished playouts
+    playouts: usize,
+    /// The number of moves played in all playouts
+    plies: usize,
+}
+
+/// Statistics about the playouts, kept apart for each policy by its name
+pub struct PlayoutStats {
+    policies: Mutex<HashMap<String, PolicyStats>>,
+}
+
+impl PlayoutStats {
+    pub fn new() -> PlayoutStats {
+        PlayoutStats {
+            policies: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records a finished playout of the policy
+    pub fn record(&self, policy: &dyn PlayoutPolicy, plies: usize) {
+        let mut policies = self.policies.lock().unwrap();
+        let stats = policies.entry(policy.name()).or_default();
+        stats.playouts += 1;
+        stats.plies += plies;
+    }
+
+    fn get(&self, policy: &dyn PlayoutPolicy) -> PolicyStats {
+        let policies = self.policies.lock().unwrap();
+        policies.get(&policy.name()).cloned().unwrap_or_default()
+    }
+
+    /// The number of finished playouts of the policy
+    pub fn playouts(&self, policy: &dyn PlayoutPolicy) -> usize {
+        self.get(policy).playouts
+    }
+
+    /// The average number of moves in a playout of the policy
+    pub fn avg_length(&self, policy: &dyn PlayoutPolicy) -> f32 {
+        let stats = self.get(policy);
+        if stats.playouts == 0 {
+            0.
+        } else {
+            stats.plies as f32 / stats.playouts as f32
+        }
+    }
+
+    /// Describes the statistics of the given policy
+    pub fn info_str(&self, policy: &dyn PlayoutPolicy) -> String {
+        format!(
+            "playouts: {} x{}, avg {:.1} plies",
+            policy.name(),
+            self.playouts(policy),
+            self.avg_length(policy)
+        )
+    }
+}
+
+/// Parses a playout policy like `random`, `capture`, `safe`, `greedy:0.1` or `mate+capture`.
+///
+/// Only `greedy` takes a parameter, its chance of a random move from 0 to 1.
+pub fn parse_playouts(spec: &str) -> Option<Box<dyn PlayoutPolicy>> {
+    if let Some(inner) = spec.strip_prefix("mate+") {
+        return Some(Box::new(MateInOne::new(parse_playouts(inner)?)));
+    }
+
+    let mut parts = spec.splitn(2, ':');
+    let name = parts.next()?;
+    let epsilon = match parts.next() {
+        Some(value) => Some(
+            value
+                .parse::<f32>()
+                .ok()
+                .filter(|epsilon| (0. ..=1.).contains(epsilon))?,
+        ),
+        None => None,
+    };
+
+    match name {
+        "random" if epsilon.is_none() => Some(Box::new(RandomPlayouts::new())),
+        "capture" if epsilon.is_none() => Some(Box::new(CaptureFirst::new())),
+        "safe" if epsilon.is_none() => Some(Box::new(AvoidBlunders::new())),
+        "greedy" => Some(Box::new(EpsilonGreedy::new(epsilon.unwrap_or(0.1)))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn choose(policy: &dyn PlayoutPolicy, board: &Board) -> BitMove {
+        policy.choose_move(board, &board.generate_moves(), &mut rand::thread_rng())
+    }
+
+    #[test]
+    fn policies_are_parsed_with_valid_parameters() {
+        for spec in [
+            "random",
+            "capture",
+            "safe",
+            "greedy",
+            "greedy:0",
+            "greedy:1",
+            "mate+greedy:0.2",
+        ]
+        .iter()
+        {
+            assert!(parse_playouts(spec).is_some(), "{}", spec);
+        }
+        for spec in [
+            "greedy:-0.1",
+            "greedy:1.5",
+            "greedy:NaN",
+            "greedy:inf",
+            "capture:0.5",
+            "mate+",
+            "mate+x",
+        ]
+        .iter()
+        {
+            assert!(parse_playouts(spec).is_none(), "{}", spec);
+        }
+        assert_eq!(
+            parse_playouts("mate+greedy:0.2").unwrap().name(),
+            "mate+greedy:0.2"
+        );
+    }
+
+    #[test]
+    fn capture_first_prefers_captures() {
+        // The only capture is exd5
+        let board = Board::from_fen("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2")
+            .unwrap();
+        for _ in 0..50 {
+            assert_eq!(choose(&CaptureFirst::new(), &board).stringify(), "e4d5");
+        }
+    }
+
+    #[test]
+    fn mate_in_one_is_always_played() {
+        // Ra8 mates, the other rook moves don't
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let policy = MateInOne::new(Box::new(RandomPlayouts::new()));
+        for _ in 0..50 {
+            assert_eq!(choose(&policy, &board).stringify(), "a1a8");
+        }
+    }
+
+    #[test]
+    fn greedy_without_epsilon_is_deterministic() {
+        // Taking the queen is the best move by far
+        let board = Board::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+        let policy = EpsilonGreedy::new(0.);
+        for _ in 0..50 {
+            assert_eq!(choose(&policy, &board).stringify(), "d2d5");
+        }
+    }
+
+    #[test]
+    fn stats_are_kept_for_each_policy() {
+        let stats = PlayoutStats::new();
+        let random = RandomPlayouts::new();
+        let greedy = EpsilonGreedy::new(0.1);
+        stats.record(&random, 10);
+        stats.record(&random, 20);
+        stats.record(&greedy, 4);
+
+        assert_eq!(stats.playouts(&random), 2);
+        assert_eq!(stats.avg_length(&random), 15.);
+        assert_eq!(stats.playouts(&greedy), 1);
+        assert_eq!(stats.avg_length(&greedy), 4.);
+        assert_eq!(stats.playouts(&CaptureFirst::new()), 0);
+        assert_eq!(
+            stats.info_str(&greedy),
+            "playouts: greedy:0.1 x1, avg 4.0 plies"
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use super::eval::{piece_value, psq_value};
+use pleco::{BitMove, Board, MoveList, PieceType};
+
+/// Bonus for moves that give check, in centipawns
+const CHECK_BONUS: i32 = 50;
+
+/// A strategy to determine the prior probability of each move
+pub trait PriorPolicy: Send {
+    /// Determines the prior of each move, in the order of the given moves.
+    ///
+    /// The priors sum up to 1.
+    fn priors(&self, board: &Board, moves: &MoveList) -> Vec<f32>;
+}
+
+/// Treats every move equally
+pub struct UniformPriors {}
+
+impl UniformPriors {
+    pub fn new() -> UniformPriors {
+        UniformPriors {}
+    }
+}
+
+impl PriorPolicy for UniformPriors {
+    fn priors(&self, _board: &Board, moves: &MoveList) -> Vec<f32> {
+        let prior = 1. / moves.len() as f32;
+        vec![prior; moves.len()]
+    }
+}
+
+/// Scores moves with cheap chess knowledge
+pub struct HeuristicPriors {
+    /// How much the priors are flattened, in centipawns
+    pub temperature: f32,
+}
+
+impl HeuristicPriors {
+    pub fn new(temperature: f32) -> HeuristicPriors {
+        HeuristicPriors { temperature }
+    }
+
+    /// Estimates how good the move is, in centipawns
+    pub fn move_score(board: &Board, mv: BitMove) -> i32 {
+        let piece = board.moved_piece(mv);
+        let piece_type = piece.type_of();
+        let mut score = 0;
+
+        // Captures: Most valuable victim, least valuable attacker
+        if mv.is_capture() {
+            let victim = if mv.is_en_passant() {
+                PieceType::P
+            } else {
+                board.captured_piece(mv)
+            };
+            score += piece_value(victim) - piece_value(piece_type) / 10;
+        }
+
+        // Promotions
+        if mv.is_promo() {
+            score += piece_value(mv.promo_piece()) - piece_value(PieceType::P);
+        }
+
+        // Checks
+        if board.gives_check(mv) {
+            score += CHECK_BONUS;
+        }
+
+        // Piece-square deltas
+        score += psq_value(piece, mv.get_dest()) - psq_value(piece, mv.get_src());
+
+        // Don't hang pieces: Losing the exchange on the destination square
+        if !board.see_ge(mv, 0) {
+            score -= piece_value(piece_type);
+        }
+
+        score
+    }
+}
+
+impl Default for HeuristicPriors {
+    fn default() -> Self {
+        HeuristicPriors::new(100.)
+    }
+}
+
+impl PriorPolicy for HeuristicPriors {
+    fn priors(&self, board: &Board, moves: &MoveList) -> Vec<f32> {
+        let scores: Vec<f32> = moves
+            .iter()
+            .map(|mv| HeuristicPriors::move_score(board, *mv) as f32 / self.temperature)
+            .collect();
+
+        // Softmax, shifted by the maximum to avoid overflows
+        let max_score = scores.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        let weights: Vec<f32> = scores.iter().map(|s| (s - max_score).exp()).collect();
+        let sum: f32 = weights.iter().sum();
+        weights.iter().map(|w| w / sum).collect()
+    }
+}
+
+/// Parses a prior policy like `uniform` or `heuristic:100`
+pub fn parse_priors(spec: &str) -> Option<Box<dyn PriorPolicy>> {
+    let mut parts = spec.splitn(2, ':');
+    let name = parts.next()?;
+    let temperature = match parts.next() {
+        // Dividing by the temperature needs a positive number
+        Some(value) => Some(
+            value
+                .parse::<f32>()
+                .ok()
+                .filter(|temperature| *temperature > 0. && temperature.is_finite())?,
+        ),
+        None => None,
+    };
+
+    match name {
+        "uniform" => Some(Box::new(UniformPriors::new())),
+        "heuristic" => Some(Box::new(match temperature {
+            Some(temperature) => HeuristicPriors::new(temperature),
+            None => HeuristicPriors::default(),
+        })),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_priors_rejects_non_positive_temperatures() {
+        assert!(parse_priors("heuristic").is_some());
+        assert!(parse_priors("heuristic:50").is_some());
+        assert!(parse_priors("heuristic:0").is_none());
+        assert!(parse_priors("heuristic:-100").is_none());
+        assert!(parse_priors("heuristic:inf").is_none());
+        assert!(parse_priors("heuristic:NaN").is_none());
+    }
+
+    #[test]
+    fn heuristic_priors_sum_up_to_one() {
+        let board = Board::start_pos();
+        let moves = board.generate_moves();
+        let priors = HeuristicPriors::default().priors(&board, &moves);
+        assert_eq!(priors.len(), moves.len());
+        assert!((priors.iter().sum::<f32>() - 1.).abs() < 1e-5);
+        assert!(priors.iter().all(|prior| prior.is_finite() && *prior > 0.));
+    }
+}
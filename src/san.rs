@@ -0,0 +1,259 @@
+use pleco::{BitMove, Board, PieceType};
+
+/// Converts a move into standard algebraic notation, like `Nbd7`, `exd5=Q` or `O-O+`
+pub fn to_san(board: &Board, mv: BitMove) -> String {
+    let mut san = if mv.is_castle() {
+        if mv.is_king_castle() {
+            String::from("O-O")
+        } else {
+            String::from("O-O-O")
+        }
+    } else {
+        let src = mv.get_src();
+        let dest = mv.get_dest();
+        let piece_type = board.moved_piece(mv).type_of();
+        let mut san = String::new();
+
+        if piece_type == PieceType::P {
+            // Pawn captures are named by their file
+            if mv.is_capture() {
+                san.push(file_char(src.file_idx_of_sq()));
+            }
+        } else {
+            san.push(piece_type.char_upper());
+            san += &disambiguation(board, mv, piece_type);
+        }
+
+        if mv.is_capture() {
+            san.push('x');
+        }
+        san += &dest.to_string();
+        if mv.is_promo() {
+            san.push('=');
+            san.push(mv.promo_piece().char_upper());
+        }
+        san
+    };
+
+    let mut next_board = board.clone();
+    next_board.apply_move(mv);
+    if next_board.checkmate() {
+        san.push('#');
+    } else if next_board.in_check() {
+        san.push('+');
+    }
+    san
+}
+
+/// Determines the part of the source square that distinguishes the move
+/// from moves of other pieces of the same type to the same square
+fn disambiguation(board: &Board, mv: BitMove, piece_type: PieceType) -> String {
+    let src = mv.get_src();
+    let others: Vec<BitMove> = board
+        .generate_moves()
+        .iter()
+        .cloned()
+        .filter(|other| {
+            other.get_dest() == mv.get_dest()
+                && other.get_src() != src
+                && board.moved_piece(*other).type_of() == piece_type
+        })
+        .collect();
+
+    if others.is_empty() {
+        String::new()
+    } else if others
+        .iter()
+        .all(|other| other.get_src().file_idx_of_sq() != src.file_idx_of_sq())
+    {
+        file_char(src.file_idx_of_sq()).to_string()
+    } else if others
+        .iter()
+        .all(|other| other.get_src().rank_idx_of_sq() != src.rank_idx_of_sq())
+    {
+        rank_char(src.rank_idx_of_sq()).to_string()
+    } else {
+        src.to_string()
+    }
+}
+
+fn file_char(file: u8) -> char {
+    (b'a' + file) as char
+}
+
+fn rank_char(rank: u8) -> char {
+    (b'1' + rank) as char
+}
+
+/// Finds the legal move written in standard algebraic notation.
+///
+/// Annotations like `+` or `!?` are ignored, and superfluous disambiguation is accepted.
+pub fn from_san(board: &Board, san: &str) -> Option<BitMove> {
+    match matching_moves(board, san, &board.generate_moves()).as_slice() {
+        [mv] => Some(*mv),
+        // Unknown or ambiguous
+        _ => None,
+    }
+}
+
+/// Finds the moves of the list that fit the standard algebraic notation,
+/// which are several if it is ambiguous
+pub fn matching_moves(board: &Board, san: &str, moves: &[BitMove]) -> Vec<BitMove> {
+    let san = san.trim_end_matches(['+', '#', '!', '?']).replace('0', "O");
+
+    if san == "O-O" || san == "O-O-O" {
+        return moves
+            .iter()
+            .cloned()
+            .filter(|mv| mv.is_castle() && mv.is_king_castle() == (san == "O-O"))
+            .collect();
+    }
+
+    // Promotion, like e8=Q or e8Q
+    let (san, promo) = match san.char_indices().last() {
+        Some((index, c)) if index > 0 && piece_type(c).is_some() => {
+            (san[..index].trim_end_matches('='), piece_type(c))
+        }
+        _ => (san.as_str(), None),
+    };
+
+    let mut chars: Vec<char> = san.chars().filter(|c| *c != 'x' && *c != '-').collect();
+    let moved = match chars.first().and_then(|c| piece_type(*c)) {
+        Some(piece_type) => {
+            chars.remove(0);
+            piece_type
+        }
+        None => PieceType::P,
+    };
+    if chars.len() < 2 {
+        return Vec::new();
+    }
+    let dest: String = chars[chars.len() - 2..].iter().collect();
+    let hints = &chars[..chars.len() - 2];
+
+    moves
+        .iter()
+        .cloned()
+        .filter(|mv| {
+            let src = mv.get_src();
+            !mv.is_castle()
+                && board.moved_piece(*mv).type_of() == moved
+                && mv.get_dest().to_string() == dest
+                && (mv.is_promo() && Some(mv.promo_piece()) == promo
+                    || !mv.is_promo() && promo.is_none())
+                && hints.iter().all(|hint| {
+                    *hint == file_char(src.file_idx_of_sq())
+                        || *hint == rank_char(src.rank_idx_of_sq())
+                })
+        })
+        .collect()
+}
+
+/// The piece type of an upper case SAN letter
+fn piece_type(c: char) -> Option<PieceType> {
+    match c {
+        'N' => Some(PieceType::N),
+        'B' => Some(PieceType::B),
+        'R' => Some(PieceType::R),
+        'Q' => Some(PieceType::Q),
+        'K' => Some(PieceType::K),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The standard algebraic notation of the move given like `e2e4`
+    fn san(fen: &str, uci: &str) -> String {
+        let board = Board::from_fen(fen).unwrap();
+        let mv = board
+            .generate_moves()
+            .iter()
+            .cloned()
+            .find(|mv| mv.stringify() == uci)
+            .unwrap();
+        to_san(&board, mv)
+    }
+
+    #[test]
+    fn to_san_writes_standard_notation() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(san(start, "e2e4"), "e4");
+        assert_eq!(san(start, "g1f3"), "Nf3");
+        // Disambiguation by file, rank and square
+        assert_eq!(san("4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1", "b1d2"), "Nbd2");
+        assert_eq!(san("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1", "a1a3"), "R1a3");
+        assert_eq!(san("4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1", "a1b2"), "Qa1b2");
+        assert_eq!(san("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), "exd6");
+        assert_eq!(san("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8q"), "b8=Q+");
+        assert_eq!(san("6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1", "a1a8"), "Ra8#");
+    }
+
+    #[test]
+    fn castling_is_written_with_letters() {
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let castles: Vec<String> = board
+            .generate_moves()
+            .iter()
+            .filter(|mv| mv.is_castle())
+            .map(|mv| to_san(&board, *mv))
+            .collect();
+        assert_eq!(castles.len(), 2);
+        assert!(castles.contains(&String::from("O-O")));
+        assert!(castles.contains(&String::from("O-O-O")));
+    }
+
+    #[test]
+    fn moves_have_distinct_notations() {
+        // A position with castling, en passant, promotions and pins
+        let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let board = Board::from_fen(kiwipete).unwrap();
+        let mut sans: Vec<String> = board
+            .generate_moves()
+            .iter()
+            .map(|mv| to_san(&board, *mv))
+            .collect();
+        let count = sans.len();
+        sans.sort();
+        sans.dedup();
+        assert_eq!(sans.len(), count);
+    }
+
+    #[test]
+    fn every_move_survives_a_round_trip() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1",
+            "4k3/1P6/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1",
+        ];
+        for fen in fens.iter() {
+            let board = Board::from_fen(fen).unwrap();
+            for mv in board.generate_moves().iter() {
+                let san = to_san(&board, *mv);
+                assert_eq!(from_san(&board, &san), Some(*mv), "{} in {}", san, fen);
+            }
+        }
+    }
+
+    #[test]
+    fn from_san_accepts_variants_and_rejects_ambiguity() {
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert!(from_san(&board, "O-O").unwrap().is_king_castle());
+        assert!(from_san(&board, "0-0-0").unwrap().is_queen_castle());
+
+        let board = Board::from_fen("4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1").unwrap();
+        assert_eq!(from_san(&board, "Nd2"), None);
+        assert!(from_san(&board, "Nbd2!?").is_some());
+        assert!(from_san(&board, "Nb1-d2").is_some());
+        assert_eq!(from_san(&board, "Nd3"), None);
+
+        let board = Board::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(from_san(&board, "b8=N").unwrap().is_promo());
+        assert!(from_san(&board, "b8Q+").unwrap().is_promo());
+        assert_eq!(from_san(&board, "b8"), None);
+    }
+}
@@ -0,0 +1,245 @@
+use super::mcts::{MCTree, MCTreeMove};
+
+use std::f32::consts::SQRT_2;
+
+/// A strategy to determine which node to expand next
+pub trait SelectionPolicy: Send {
+    /// Determines how valuable it is to expand the node of the given move.
+    ///
+    /// The node is the (already locked) node the move leads to.
+    fn select_value(&self, mv: &MCTreeMove, node: &MCTree, parent_playouts: usize) -> f32;
+
+    /// Whether the policy reads the priors of the moves, which are costly to compute
+    fn uses_priors(&self) -> bool {
+        false
+    }
+}
+
+/// Upper Confidence bounds applied to Trees
+pub struct Uct {
+    /// The weight of the exploration term
+    pub exploration: f32,
+}
+
+impl Uct {
+    pub fn new(exploration: f32) -> Uct {
+        Uct { exploration }
+    }
+}
+
+impl Default for Uct {
+    fn default() -> Self {
+        Uct::new(SQRT_2)
+    }
+}
+
+impl SelectionPolicy for Uct {
+    fn select_value(&self, mv: &MCTreeMove, node: &MCTree, parent_playouts: usize) -> f32 {
+        // Exploitation: Exploit potentially good moves.
+        // The statistics of the node are shared by all transpositions.
+        let exploitation = node.play_value();
+        // Exploration: Explore rarely investigated moves.
+        // Only the playouts through this move count, not through transpositions.
+        let exploration = if mv.playouts == 0 {
+            1.
+        } else {
+            self.exploration * ((parent_playouts as f32).ln() / (mv.playouts as f32)).sqrt()
+        };
+        exploitation + exploration
+    }
+}
+
+/// UCB1-Tuned, which scales the exploration by the variance of the node
+pub struct Ucb1Tuned {}
+
+impl Ucb1Tuned {
+    pub fn new() -> Ucb1Tuned {
+        Ucb1Tuned {}
+    }
+}
+
+impl SelectionPolicy for Ucb1Tuned {
+    fn select_value(&self, mv: &MCTreeMove, node: &MCTree, parent_playouts: usize) -> f32 {
+        let exploitation = node.play_value();
+        let exploration = if mv.playouts == 0 {
+            1.
+        } else {
+            let log_term = (parent_playouts as f32).ln() / (mv.playouts as f32);
+            // Upper bound of the variance of the node
+            let variance = node.variance() + (2. * log_term).sqrt();
+            // The variance of a Bernoulli variable is at most 1/4
+            let variance = if variance < 0.25 { variance } else { 0.25 };
+            (log_term * variance).sqrt()
+        };
+        exploitation + exploration
+    }
+}
+
+/// Predictor + UCT, which guides the exploration by the prior of each move
+pub struct Puct {
+    /// The weight of the exploration term
+    pub exploration: f32,
+}
+
+impl Puct {
+    pub fn new(exploration: f32) -> Puct {
+        Puct { exploration }
+    }
+}
+
+impl SelectionPolicy for Puct {
+    fn select_value(&self, mv: &MCTreeMove, node: &MCTree, parent_playouts: usize) -> f32 {
+        let exploitation = node.play_value();
+        // Moves with a high prior are explored first
+        let exploration = self.exploration * mv.prior * (parent_playouts as f32).sqrt()
+            / (1 + mv.playouts) as f32;
+        exploitation + exploration
+    }
+
+    fn uses_priors(&self) -> bool {
+        true
+    }
+}
+
+/// UCT with a progressive bias towards moves with a high prior
+pub struct ProgressiveBias {
+    /// The weight of the exploration term
+    pub exploration: f32,
+    /// The weight of the prior, which fades with more playouts
+    pub bias: f32,
+}
+
+impl ProgressiveBias {
+    pub fn new(exploration: f32, bias: f32) -> ProgressiveBias {
+        ProgressiveBias { exploration, bias }
+    }
+}
+
+impl SelectionPolicy for ProgressiveBias {
+    fn select_value(&self, mv: &MCTreeMove, node: &MCTree, parent_playouts: usize) -> f32 {
+        let uct = Uct::new(self.exploration).select_value(mv, node, parent_playouts);
+        uct + self.bias * mv.prior / (1 + mv.playouts) as f32
+    }
+
+    fn uses_priors(&self) -> bool {
+        true
+    }
+}
+
+/// Parses a selection policy like `uct`, `uct:0.7`, `ucb1-tuned`, `puct:1.5` or `bias:1`.
+///
+/// The weights must be positive, UCB1-Tuned has none.
+pub fn parse_policy(spec: &str) -> Option<Box<dyn SelectionPolicy>> {
+    let mut parts = spec.splitn(2, ':');
+    let name = parts.next()?;
+    let exploration = match parts.next() {
+        Some(value) => Some(
+            value
+                .parse::<f32>()
+                .ok()
+                .filter(|value| value.is_finite() && *value > 0.)?,
+        ),
+        None => None,
+    };
+
+    match name {
+        "uct" => Some(Box::new(Uct::new(exploration.unwrap_or(SQRT_2)))),
+        "ucb1-tuned" if exploration.is_none() => Some(Box::new(Ucb1Tuned::new())),
+        "puct" => Some(Box::new(Puct::new(exploration.unwrap_or(1.5)))),
+        "bias" => Some(Box::new(ProgressiveBias::new(
+            SQRT_2,
+            exploration.unwrap_or(1.),
+        ))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pleco::{BitMove, Board};
+    use std::sync::{Arc, Mutex};
+
+    /// A move with the given prior and playouts to a node with the given value
+    fn child(prior: f32, playouts: usize, value: f32) -> (MCTreeMove, MCTree) {
+        let mut node = MCTree::new(&Board::start_pos());
+        node.playouts = playouts;
+        // The value of a node is the win rate of the player who moved into it
+        node.wins = ((1. - value) * playouts as f32).round() as usize;
+        let mut mv = MCTreeMove::new(BitMove::null(), prior, Arc::new(Mutex::new(node.clone())));
+        mv.playouts = playouts;
+        (mv, node)
+    }
+
+    fn value(policy: &dyn SelectionPolicy, child: &(MCTreeMove, MCTree), parent: usize) -> f32 {
+        policy.select_value(&child.0, &child.1, parent)
+    }
+
+    #[test]
+    fn policies_are_parsed_with_positive_weights() {
+        for spec in ["uct", "uct:0.7", "ucb1-tuned", "puct", "puct:1.5", "bias:1"].iter() {
+            assert!(parse_policy(spec).is_some(), "{}", spec);
+        }
+        for spec in [
+            "uct:0",
+            "uct:-1",
+            "uct:NaN",
+            "uct:inf",
+            "uct:",
+            "puct:x",
+            "bias:-0.5",
+            "ucb1-tuned:2",
+            "ucb",
+        ]
+        .iter()
+        {
+            assert!(parse_policy(spec).is_none(), "{}", spec);
+        }
+        assert!(parse_policy("puct").unwrap().uses_priors());
+        assert!(!parse_policy("uct").unwrap().uses_priors());
+    }
+
+    #[test]
+    fn unvisited_moves_are_preferred() {
+        let unvisited = child(0.1, 0, 0.5);
+        let explored = child(0.1, 500, 0.7);
+        let policies: [Box<dyn SelectionPolicy>; 4] = [
+            Box::new(Uct::default()),
+            Box::new(Ucb1Tuned::new()),
+            Box::new(Puct::new(1.5)),
+            Box::new(ProgressiveBias::new(SQRT_2, 1.)),
+        ];
+        for policy in policies.iter() {
+            assert!(value(&**policy, &unvisited, 1000) > value(&**policy, &explored, 1000));
+        }
+    }
+
+    #[test]
+    fn puct_explores_moves_with_a_high_prior() {
+        let puct = Puct::new(1.5);
+        assert!(value(&puct, &child(0.6, 0, 0.5), 100) > value(&puct, &child(0.1, 0, 0.5), 100));
+        assert!(value(&puct, &child(0.6, 10, 0.5), 100) > value(&puct, &child(0.1, 10, 0.5), 100));
+        // A good value outweighs the prior once the move is explored
+        assert!(value(&puct, &child(0.1, 50, 0.8), 100) > value(&puct, &child(0.3, 50, 0.5), 100));
+        // Uct ignores the prior
+        let uct = Uct::default();
+        assert_eq!(
+            value(&uct, &child(0.6, 10, 0.5), 100),
+            value(&uct, &child(0.1, 10, 0.5), 100)
+        );
+    }
+
+    #[test]
+    fn progressive_bias_fades_with_playouts() {
+        let uct = Uct::default();
+        let bias = ProgressiveBias::new(SQRT_2, 1.);
+        let bias_term = |playouts| {
+            let child = child(0.5, playouts, 0.5);
+            value(&bias, &child, 10_000) - value(&uct, &child, 10_000)
+        };
+        assert!((bias_term(0) - 0.5).abs() < 1e-6);
+        assert!((bias_term(9) - 0.05).abs() < 1e-6);
+        assert!(bias_term(999) < 1e-3);
+        assert!(bias_term(999) > 0.);
+    }
+}
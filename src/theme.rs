@@ -0,0 +1,467 @@
+use super::player_spec::option_value;
+use colored::control::SHOULD_COLORIZE;
+use colored::Color;
+use pleco::{Piece, PieceType, Player};
+
+/// The usual look of the 16 standard colors, in the order of the palette
+const BASIC_RGB: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (170, 0, 0),
+    (0, 170, 0),
+    (170, 85, 0),
+    (0, 0, 170),
+    (170, 0, 170),
+    (0, 170, 170),
+    (170, 170, 170),
+    (85, 85, 85),
+    (255, 85, 85),
+    (85, 255, 85),
+    (255, 255, 85),
+    (85, 85, 255),
+    (255, 85, 255),
+    (85, 255, 255),
+    (255, 255, 255),
+];
+
+/// A color of the terminal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThemeColor {
+    /// One of the 16 standard colors
+    Basic(Color),
+    /// A color of the 256-color palette
+    Palette(u8),
+    /// A truecolor, given by its red, green and blue parts
+    Rgb(u8, u8, u8),
+}
+
+impl ThemeColor {
+    /// Parses a color like `bright-blue`, a palette index like `67` or a truecolor like `#b58863`
+    pub fn parse(spec: &str) -> Option<ThemeColor> {
+        if let Some(hex) = spec.strip_prefix('#') {
+            if hex.len() != 6 {
+                return None;
+            }
+            let part = |index: usize| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok();
+            return Some(ThemeColor::Rgb(part(0)?, part(2)?, part(4)?));
+        }
+        if let Ok(index) = spec.parse::<u8>() {
+            return Some(ThemeColor::Palette(index));
+        }
+        let color = match spec {
+            "black" => Color::Black,
+            "red" => Color::Red,
+            "green" => Color::Green,
+            "yellow" => Color::Yellow,
+            "blue" => Color::Blue,
+            "magenta" => Color::Magenta,
+            "cyan" => Color::Cyan,
+            "white" => Color::White,
+            "bright-black" => Color::BrightBlack,
+            "bright-red" => Color::BrightRed,
+            "bright-green" => Color::BrightGreen,
+            "bright-yellow" => Color::BrightYellow,
+            "bright-blue" => Color::BrightBlue,
+            "bright-magenta" => Color::BrightMagenta,
+            "bright-cyan" => Color::BrightCyan,
+            "bright-white" => Color::BrightWhite,
+            _ => return None,
+        };
+        Some(ThemeColor::Basic(color))
+    }
+
+    /// The escape code parameters to use the color for the text
+    fn fg_code(self) -> String {
+        match self {
+            ThemeColor::Basic(color) => String::from(color.to_fg_str()),
+            ThemeColor::Palette(index) => format!("38;5;{}", index),
+            ThemeColor::Rgb(r, g, b) => format!("38;2;{};{};{}", r, g, b),
+        }
+    }
+
+    /// The escape code parameters to use the color for the background
+    fn bg_code(self) -> String {
+        match self {
+            ThemeColor::Basic(color) => String::from(color.to_bg_str()),
+            ThemeColor::Palette(index) => format!("48;5;{}", index),
+            ThemeColor::Rgb(r, g, b) => format!("48;2;{};{};{}", r, g, b),
+        }
+    }
+
+    /// The approximate red, green and blue parts of the color
+    fn rgb(self) -> (u8, u8, u8) {
+        match self {
+            ThemeColor::Basic(color) => BASIC_RGB[basic_index(color)],
+            ThemeColor::Palette(index) if index < 16 => BASIC_RGB[index as usize],
+            ThemeColor::Palette(index) if index < 232 => {
+                // The 6x6x6 color cube
+                let level = |value: u8| if value == 0 { 0 } else { 55 + 40 * value };
+                let index = index - 16;
+                (level(index / 36), level(index / 6 % 6), level(index % 6))
+            }
+            ThemeColor::Palette(index) => {
+                // The gray ramp
+                let gray = 8 + 10 * (index - 232);
+                (gray, gray, gray)
+            }
+            ThemeColor::Rgb(r, g, b) => (r, g, b),
+        }
+    }
+
+    /// Black or white, whichever can be read better on this color
+    fn readable_text(self) -> ThemeColor {
+        let (r, g, b) = self.rgb();
+        let luminance = 299 * r as u32 + 587 * g as u32 + 114 * b as u32;
+        if luminance > 128_000 {
+            ThemeColor::Basic(Color::Black)
+        } else {
+            ThemeColor::Basic(Color::White)
+        }
+    }
+}
+
+/// The index of a standard color in the palette
+fn basic_index(color: Color) -> usize {
+    match color {
+        Color::Black => 0,
+        Color::Red => 1,
+        Color::Green => 2,
+        Color::Yellow => 3,
+        Color::Blue => 4,
+        Color::Magenta => 5,
+        Color::Cyan => 6,
+        Color::White => 7,
+        Color::BrightBlack => 8,
+        Color::BrightRed => 9,
+        Color::BrightGreen => 10,
+        Color::BrightYellow => 11,
+        Color::BrightBlue => 12,
+        Color::BrightMagenta => 13,
+        Color::BrightCyan => 14,
+        Color::BrightWhite => 15,
+    }
+}
+
+/// The background and text color of a square
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SquareColors {
+    bg: ThemeColor,
+    fg: ThemeColor,
+}
+
+impl SquareColors {
+    fn new(bg: ThemeColor, fg: ThemeColor) -> SquareColors {
+        SquareColors { bg, fg }
+    }
+
+    /// The background with a readable text color
+    fn readable(bg: ThemeColor) -> SquareColors {
+        SquareColors {
+            bg,
+            fg: bg.readable_text(),
+        }
+    }
+
+    fn paint(self, text: &str) -> String {
+        format!(
+            "\x1B[{};{}m{}\x1B[0m",
+            self.fg.fg_code(),
+            self.bg.bg_code(),
+            text
+        )
+    }
+}
+
+/// Why a square stands out
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Highlight {
+    None,
+    /// The source or destination of the last move
+    LastMove,
+    /// The square of the piece captured by the last move
+    Capture,
+    /// The king in check
+    Check,
+    /// The square under the cursor of the full-screen mode
+    Cursor,
+    /// The piece picked up in the full-screen mode
+    Selected,
+    /// A legal destination of the piece picked up or shown by an overlay
+    Target,
+    /// A square attacked by the player shown by an overlay
+    Attacked,
+    /// A piece that is attacked and not defended
+    Hanging,
+}
+
+impl Highlight {
+    /// The marker next to highlighted pieces when there are no colors
+    fn marker(self) -> char {
+        match self {
+            Highlight::None => ' ',
+            Highlight::LastMove => '*',
+            Highlight::Capture => 'x',
+            Highlight::Check => '+',
+            Highlight::Cursor => '<',
+            Highlight::Selected => '>',
+            Highlight::Target => 'o',
+            Highlight::Attacked => ':',
+            Highlight::Hanging => '!',
+        }
+    }
+}
+
+/// How the board is drawn: the colors of the squares and the symbols of the pieces.
+///
+/// The colors are indexed by the highlight and then light or dark square.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    normal: [SquareColors; 2],
+    last_move: [SquareColors; 2],
+    capture: [SquareColors; 2],
+    check: [SquareColors; 2],
+    cursor: [SquareColors; 2],
+    selected: [SquareColors; 2],
+    target: [SquareColors; 2],
+    attacked: [SquareColors; 2],
+    hanging: [SquareColors; 2],
+    /// Draws without colors, marking the highlights with symbols
+    pub monochrome: bool,
+    /// Draws the pieces as Unicode chess glyphs instead of letters
+    pub glyphs: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::from_name("classic").unwrap().with_fallback()
+    }
+}
+
+impl Theme {
+    /// The built-in themes, like `classic` with the 16 standard colors,
+    /// `blue` with the 256-color palette or `wood` with truecolors
+    pub fn from_name(name: &str) -> Option<Theme> {
+        use ThemeColor::{Basic, Palette, Rgb};
+        let pairs = |light: ThemeColor, dark: ThemeColor| {
+            [SquareColors::readable(light), SquareColors::readable(dark)]
+        };
+
+        let theme = match name {
+            "classic" => {
+                let (black, white) = (Basic(Color::Black), Basic(Color::White));
+                Theme {
+                    normal: [
+                        SquareColors::new(white, black),
+                        SquareColors::new(Basic(Color::Black), white),
+                    ],
+                    last_move: [
+                        SquareColors::new(Basic(Color::BrightBlue), black),
+                        SquareColors::new(Basic(Color::Blue), white),
+                    ],
+                    capture: [
+                        SquareColors::new(Basic(Color::BrightRed), black),
+                        SquareColors::new(Basic(Color::Red), white),
+                    ],
+                    check: [
+                        SquareColors::new(Basic(Color::BrightYellow), black),
+                        SquareColors::new(Basic(Color::Yellow), black),
+                    ],
+                    cursor: [
+                        SquareColors::new(Basic(Color::BrightGreen), black),
+                        SquareColors::new(Basic(Color::Green), black),
+                    ],
+                    selected: [
+                        SquareColors::new(Basic(Color::BrightMagenta), black),
+                        SquareColors::new(Basic(Color::Magenta), white),
+                    ],
+                    target: [
+                        SquareColors::new(Basic(Color::BrightCyan), black),
+                        SquareColors::new(Basic(Color::Cyan), black),
+                    ],
+                    attacked: [
+                        SquareColors::new(Basic(Color::BrightBlack), black),
+                        SquareColors::new(Basic(Color::BrightBlack), white),
+                    ],
+                    hanging: [
+                        SquareColors::new(Basic(Color::BrightRed), white),
+                        SquareColors::new(Basic(Color::Red), black),
+                    ],
+                    monochrome: false,
+                    glyphs: false,
+                }
+            }
+            "blue" => Theme {
+                normal: pairs(Palette(153), Palette(67)),
+                last_move: pairs(Palette(186), Palette(143)),
+                capture: pairs(Palette(217), Palette(167)),
+                check: pairs(Palette(229), Palette(178)),
+                cursor: pairs(Palette(120), Palette(71)),
+                selected: pairs(Palette(219), Palette(133)),
+                target: pairs(Palette(159), Palette(73)),
+                attacked: pairs(Palette(250), Palette(244)),
+                hanging: pairs(Palette(210), Palette(160)),
+                monochrome: false,
+                glyphs: false,
+            },
+            "wood" => Theme {
+                normal: pairs(Rgb(240, 217, 181), Rgb(181, 136, 99)),
+                last_move: pairs(Rgb(205, 210, 106), Rgb(170, 162, 58)),
+                capture: pairs(Rgb(236, 126, 106), Rgb(200, 85, 65)),
+                check: pairs(Rgb(250, 230, 100), Rgb(220, 190, 40)),
+                cursor: pairs(Rgb(150, 210, 130), Rgb(100, 160, 80)),
+                selected: pairs(Rgb(220, 170, 220), Rgb(170, 110, 170)),
+                target: pairs(Rgb(170, 215, 225), Rgb(110, 160, 175)),
+                attacked: pairs(Rgb(205, 205, 205), Rgb(145, 145, 145)),
+                hanging: pairs(Rgb(255, 110, 110), Rgb(205, 45, 45)),
+                monochrome: false,
+                glyphs: false,
+            },
+            "mono" => Theme {
+                monochrome: true,
+                ..Theme::from_name("classic")?
+            },
+            _ => return None,
+        };
+        Some(theme)
+    }
+
+    /// Turns monochrome if `NO_COLOR` is set or the output isn't a terminal
+    fn with_fallback(self) -> Theme {
+        Theme {
+            monochrome: self.monochrome || !SHOULD_COLORIZE.should_colorize(),
+            ..self
+        }
+    }
+
+    /// Reads the theme options of the command line:
+    /// `--theme name`, `--light color`, `--dark color`, `--highlight color` and `--glyphs`
+    pub fn from_args(args: &[String]) -> Result<Theme, String> {
+        let mut theme = match option_value(args, "--theme") {
+            Some(name) => Theme::from_name(name).ok_or(format!(
+                "Unknown theme '{}'.\nUse classic, blue, wood or mono.",
+                name
+            ))?,
+            None => Theme::from_name("classic").unwrap(),
+        };
+        let color = |option: &str| -> Result<Option<ThemeColor>, String> {
+            match option_value(args, option) {
+                Some(spec) => ThemeColor::parse(spec).map(Some).ok_or(format!(
+                    "Invalid color '{}' of {}.\nUse a name like bright-blue, \
+                     a palette index like 67 or a truecolor like #b58863.",
+                    spec, option
+                )),
+                None => Ok(None),
+            }
+        };
+        if let Some(light) = color("--light")? {
+            theme.normal[0] = SquareColors::readable(light);
+        }
+        if let Some(dark) = color("--dark")? {
+            theme.normal[1] = SquareColors::readable(dark);
+        }
+        if let Some(highlight) = color("--highlight")? {
+            theme.last_move = [SquareColors::readable(highlight); 2];
+        }
+        theme.glyphs = args.iter().any(|arg| arg == "--glyphs");
+        Ok(theme.with_fallback())
+    }
+
+    /// The symbol of the piece, a letter or a glyph
+    pub fn piece_char(&self, piece: Piece) -> char {
+        if !self.glyphs {
+            return piece.character_lossy();
+        }
+        match (piece.player_lossy(), piece.type_of()) {
+            (Player::White, PieceType::K) => '♔',
+            (Player::White, PieceType::Q) => '♕',
+            (Player::White, PieceType::R) => '♖',
+            (Player::White, PieceType::B) => '♗',
+            (Player::White, PieceType::N) => '♘',
+            (Player::White, _) => '♙',
+            (Player::Black, PieceType::K) => '♚',
+            (Player::Black, PieceType::Q) => '♛',
+            (Player::Black, PieceType::R) => '♜',
+            (Player::Black, PieceType::B) => '♝',
+            (Player::Black, PieceType::N) => '♞',
+            (Player::Black, _) => '♟',
+        }
+    }
+
+    /// Draws a square of the board, two characters wide
+    pub fn square_str(&self, piece: Piece, light: bool, highlight: Highlight) -> String {
+        self.marked_square_str(piece, light, highlight, Highlight::None)
+    }
+
+    /// Draws a square with a second highlight, which is shown by its marker next to the piece
+    pub fn marked_square_str(
+        &self,
+        piece: Piece,
+        light: bool,
+        highlight: Highlight,
+        mark: Highlight,
+    ) -> String {
+        if self.monochrome {
+            // Both highlights need a marker, empty squares have room for two
+            let (first, second) = match (highlight, mark) {
+                (Highlight::None, _) => (Highlight::None, mark),
+                (_, Highlight::None) => (Highlight::None, highlight),
+                _ => (mark, highlight),
+            };
+            let symbol = if piece != Piece::None {
+                self.piece_char(piece)
+            } else if first != Highlight::None {
+                first.marker()
+            } else if light {
+                ' '
+            } else {
+                '.'
+            };
+            return format!("{}{}", symbol, second.marker());
+        }
+
+        let symbol = if piece != Piece::None {
+            self.piece_char(piece)
+        } else {
+            ' '
+        };
+        let colors = match highlight {
+            Highlight::None => &self.normal,
+            Highlight::LastMove => &self.last_move,
+            Highlight::Capture => &self.capture,
+            Highlight::Check => &self.check,
+            Highlight::Cursor => &self.cursor,
+            Highlight::Selected => &self.selected,
+            Highlight::Target => &self.target,
+            Highlight::Attacked => &self.attacked,
+            Highlight::Hanging => &self.hanging,
+        };
+        colors[if light { 0 } else { 1 }].paint(&format!("{}{}", symbol, mark.marker()))
+    }
+
+    /// Draws a cell of the evaluation bar, filled for white or for black
+    pub fn bar_str(&self, white: bool) -> String {
+        if self.monochrome {
+            String::from(if white { "::" } else { "##" })
+        } else {
+            self.normal[if white { 0 } else { 1 }].paint("  ")
+        }
+    }
+
+    /// Draws a piece on the square color of its owner, like in the move history
+    pub fn piece_str(&self, piece: Piece) -> String {
+        let light = piece.player_lossy() == Player::White;
+        self.square_str(piece, light, Highlight::None)
+    }
+
+    /// Draws a piece captured by the last move
+    pub fn capture_str(&self, piece: Piece) -> String {
+        // Captured white pieces are shown like light squares
+        let light = piece.player_lossy() == Player::White;
+        self.square_str(piece, light, Highlight::Capture)
+    }
+
+    /// Draws the king that is in check
+    pub fn check_str(&self, king: Piece) -> String {
+        let light = king.player_lossy() == Player::White;
+        self.square_str(king, light, Highlight::Check)
+    }
+}
@@ -0,0 +1,231 @@
+use super::elo::{bayes_elo, RatingList, DRAW_ELO, WHITE_ADVANTAGE};
+use super::match_runner::{
+    create_pgn_file, number_option, run_games, GameSettings, ScheduledGame, GAME_OPTIONS,
+};
+use super::pgn::GameResult;
+use pleco::Player;
+
+use std::io::Write;
+
+/// The number of games per pairing without the `--rounds` option
+const DEFAULT_ROUNDS: usize = 2;
+
+/// Lists the pairs of players that play each other
+fn pairings(players: usize, gauntlet: bool) -> Vec<(usize, usize)> {
+    if gauntlet {
+        // The first player against everyone else
+        (1..players).map(|opponent| (0, opponent)).collect()
+    } else {
+        (0..players)
+            .flat_map(|first| ((first + 1)..players).map(move |second| (first, second)))
+            .collect()
+    }
+}
+
+/// Plays every pairing for the given number of rounds, swapping colors every round
+fn schedule(pairings: &[(usize, usize)], rounds: usize) -> Vec<ScheduledGame> {
+    let mut games = Vec::new();
+    for round in 0..rounds {
+        for &(first, second) in pairings {
+            let (white, black) = if round.is_multiple_of(2) {
+                (first, second)
+            } else {
+                (second, first)
+            };
+            games.push(ScheduledGame {
+                index: games.len(),
+                white,
+                black,
+                // Each opening is played with both colors
+                opening: round / 2,
+            });
+        }
+    }
+    games
+}
+
+/// The results of a tournament
+struct Crosstable {
+    names: Vec<String>,
+    /// The points of a player against an opponent
+    points: Vec<Vec<f32>>,
+    /// The number of games between two players
+    games: Vec<Vec<usize>>,
+    draws: Vec<usize>,
+    /// The white player, the black player and the result of each game
+    results: Vec<(usize, usize, GameResult)>,
+}
+
+impl Crosstable {
+    fn new(names: Vec<String>) -> Crosstable {
+        let players = names.len();
+        Crosstable {
+            names,
+            points: vec![vec![0.; players]; players],
+            games: vec![vec![0; players]; players],
+            draws: vec![0; players],
+            results: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, white: usize, black: usize, result: GameResult) {
+        self.points[white][black] += result.score(Player::White);
+        self.points[black][white] += result.score(Player::Black);
+        self.games[white][black] += 1;
+        self.games[black][white] += 1;
+        if result == GameResult::Draw {
+            self.draws[white] += 1;
+            self.draws[black] += 1;
+        }
+        self.results.push((white, black, result));
+    }
+
+    fn total_points(&self, player: usize) -> f32 {
+        self.points[player].iter().sum()
+    }
+
+    fn total_games(&self, player: usize) -> usize {
+        self.games[player].iter().sum()
+    }
+
+    /// Formats the rating list and the crosstable, ordered by rating
+    fn table_str(&self, ratings: &RatingList) -> String {
+        let mut order: Vec<usize> = (0..self.names.len()).collect();
+        order.sort_by(|a, b| ratings.elo[*b].total_cmp(&ratings.elo[*a]));
+        let width = self.names.iter().map(String::len).max().unwrap_or(0).max(4);
+
+        let mut s = format!(
+            "{:>4} {:<width$} {:>6} {:>5} {:>6} {:>6} {:>6}\n",
+            "Rank",
+            "Name",
+            "Elo",
+            "+/-",
+            "Games",
+            "Score",
+            "Draws",
+            width = width
+        );
+        for (rank, &player) in order.iter().enumerate() {
+            let games = self.total_games(player).max(1) as f32;
+            s += &format!(
+                "{:>4} {:<width$} {:>+6.0} {:>5.0} {:>6} {:>5.1}% {:>5.1}%\n",
+                rank + 1,
+                self.names[player],
+                ratings.elo[player],
+                ratings.margin[player],
+                self.total_games(player),
+                100. * self.total_points(player) / games,
+                100. * self.draws[player] as f32 / games,
+                width = width
+            );
+        }
+        s += &format!(
+            "Assuming a white advantage of {} Elo and a draw Elo of {}\n\n",
+            WHITE_ADVANTAGE, DRAW_ELO
+        );
+
+        // The crosstable, with the opponents numbered by their rank
+        s += &format!("{:>4} {:<width$}", "", "Crosstable", width = width);
+        for rank in 1..=order.len() {
+            s += &format!(" {:>7}", rank);
+        }
+        s.push('\n');
+        for (rank, &player) in order.iter().enumerate() {
+            s += &format!(
+                "{:>4} {:<width$}",
+                rank + 1,
+                self.names[player],
+                width = width
+            );
+            for &opponent in order.iter() {
+                let cell = if opponent == player {
+                    String::from("-")
+                } else if self.games[player][opponent] == 0 {
+                    String::new()
+                } else {
+                    format!(
+                        "{}/{}",
+                        self.points[player][opponent], self.games[player][opponent]
+                    )
+                };
+                s += &format!(" {:>7}", cell);
+            }
+            s.push('\n');
+        }
+        s
+    }
+}
+
+/// Plays a tournament between several players, like
+/// `tournament stonefish stonefish,selection=uct:0.7 random --rounds 4`
+pub fn run_tournament(args: &[String]) -> Result<(), String> {
+    let usage = format!(
+        "Usage: tournament <player> <player> [<player>...] [--gauntlet] [--rounds n] {}",
+        GAME_OPTIONS
+    );
+    let names: Vec<String> = args
+        .iter()
+        .skip(2)
+        .take_while(|arg| !arg.starts_with("--"))
+        .cloned()
+        .collect();
+    if names.len() < 2 {
+        return Err(usage);
+    }
+    let gauntlet = args.iter().any(|arg| arg == "--gauntlet");
+    let rounds = number_option(args, "--rounds", DEFAULT_ROUNDS)
+        .map_err(|err| format!("{}\n{}", err, usage))?;
+    let settings = GameSettings::from_args(
+        String::from(if gauntlet { "Gauntlet" } else { "Round robin" }),
+        names.clone(),
+        args,
+        "tournament.pgn",
+    )
+    .map_err(|err| format!("{}\n{}", err, usage))?;
+    settings.check_players(args)?;
+    let mut pgn_file = create_pgn_file(&settings.pgn_path)?;
+    let pgn_path = settings.pgn_path.clone();
+
+    let schedule = schedule(&pairings(names.len(), gauntlet), rounds);
+    let total_games = schedule.len();
+    println!(
+        "{} of {} players: {} games, {}ms per move, {} at a time",
+        settings.event,
+        names.len(),
+        total_games,
+        settings.move_time.as_millis(),
+        settings.concurrency.min(total_games)
+    );
+
+    let mut crosstable = Crosstable::new(names);
+    let mut write_error = None;
+    let mut finished = 0;
+    run_games(settings, schedule, args, |game| {
+        finished += 1;
+        crosstable.add(game.game.white, game.game.black, game.result);
+        println!(
+            "Game {}/{}: {} vs {} {} ({})",
+            finished,
+            total_games,
+            game.pgn.white,
+            game.pgn.black,
+            game.result.pgn_str(),
+            game.pgn.termination.as_deref().unwrap_or("")
+        );
+        if let Err(err) = writeln!(pgn_file, "{}", game.pgn) {
+            write_error = Some(err);
+        }
+        true
+    });
+
+    let ratings = bayes_elo(crosstable.names.len(), &crosstable.results);
+    println!();
+    print!("{}", crosstable.table_str(&ratings));
+    match write_error {
+        Some(err) => Err(format!("Can't save the games to '{}': {}", pgn_path, err)),
+        None => {
+            println!("Games saved to {}", pgn_path);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,485 @@
+use super::cli_board::{CliBoard, Overlay};
+use pleco::{BitMove, Board, PieceType, Player, SQ};
+
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+/// How long to wait for input before the screen is redrawn
+pub const TICK: Duration = Duration::from_millis(250);
+/// The pieces offered by the promotion popup, in order
+pub const PROMOTIONS: [PieceType; 4] = [PieceType::Q, PieceType::R, PieceType::B, PieceType::N];
+
+/// Switches to the alternate screen, hides the cursor and reports mouse clicks
+const ENTER: &str = "\x1b[?1049h\x1b[?25l\x1b[?1000h\x1b[?1006h";
+/// Undoes everything of `ENTER`
+const LEAVE: &str = "\x1b[?1006l\x1b[?1000l\x1b[?25h\x1b[?1049l";
+
+/// The terminal settings before the raw mode, restored when leaving it
+static ORIGINAL: OnceLock<libc::termios> = OnceLock::new();
+/// Whether the alternate screen of the full-screen mode is shown
+static FULL_SCREEN: AtomicBool = AtomicBool::new(false);
+
+/// Unbuffered input without echo, which ends when this is dropped
+pub struct RawMode {}
+
+impl RawMode {
+    /// Lets the program read every key at once and draw the input itself
+    pub fn enable() -> Result<RawMode, String> {
+        if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+            return Err(String::from("The input and output need a terminal."));
+        }
+        let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut termios) } != 0 {
+            return Err(String::from("Can't read the terminal settings."));
+        }
+        let original = *ORIGINAL.get_or_init(|| termios);
+
+        // Read every key at once and without echo, but keep Ctrl-C
+        let mut raw = original;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+        raw.c_cc[libc::VMIN] = 0;
+        raw.c_cc[libc::VTIME] = 0;
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw);
+            libc::signal(
+                libc::SIGINT,
+                on_interrupt as *const () as libc::sighandler_t,
+            );
+        }
+        Ok(RawMode {})
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+/// The full-screen mode of the terminal, which ends when this is dropped
+pub struct Terminal {
+    _raw: RawMode,
+}
+
+impl Terminal {
+    /// Switches the terminal to the full-screen mode with unbuffered input
+    pub fn enter() -> Result<Terminal, String> {
+        let raw = RawMode::enable()
+            .map_err(|_| String::from("The full-screen mode needs a terminal."))?;
+        FULL_SCREEN.store(true, Ordering::SeqCst);
+        print!("{}", ENTER);
+        let _ = io::stdout().flush();
+        Ok(Terminal { _raw: raw })
+    }
+}
+
+/// Restores the terminal, which is safe to do more than once
+fn restore() {
+    if let Some(original) = ORIGINAL.get() {
+        unsafe {
+            if FULL_SCREEN.swap(false, Ordering::SeqCst) {
+                libc::write(
+                    libc::STDOUT_FILENO,
+                    LEAVE.as_ptr() as *const libc::c_void,
+                    LEAVE.len(),
+                );
+            }
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, original);
+        }
+    }
+}
+
+/// Restores the terminal before Ctrl-C ends the program
+extern "C" fn on_interrupt(_signal: libc::c_int) {
+    restore();
+    unsafe { libc::_exit(130) };
+}
+
+/// A key press or a mouse click
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    Enter,
+    Escape,
+    Tab,
+    Backspace,
+    Delete,
+    Char(char),
+    /// A letter typed while holding Ctrl, like `Ctrl('a')`
+    Ctrl(char),
+    /// The input was closed
+    Eof,
+    /// A click of the left mouse button at a column and row, both starting at 1
+    Click(u16, u16),
+}
+
+/// Waits up to the given time for input and returns the keys
+pub fn read_keys(timeout: Duration) -> Vec<Key> {
+    let mut poll = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    if unsafe { libc::poll(&mut poll, 1, timeout.as_millis() as libc::c_int) } <= 0 {
+        return Vec::new();
+    }
+    let mut buffer = [0u8; 64];
+    let read = unsafe {
+        libc::read(
+            libc::STDIN_FILENO,
+            buffer.as_mut_ptr() as *mut libc::c_void,
+            buffer.len(),
+        )
+    };
+    if read <= 0 {
+        return vec![Key::Eof];
+    }
+    parse_keys(&buffer[..read as usize])
+}
+
+/// Parses the bytes of the terminal input, ignoring unknown escape sequences
+fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+    let mut keys = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            0x1b if bytes.get(i + 1) == Some(&b'[') || bytes.get(i + 1) == Some(&b'O') => {
+                // The sequence ends with a letter or ~
+                let end = bytes[i + 2..]
+                    .iter()
+                    .position(|b| b.is_ascii_alphabetic() || *b == b'~')
+                    .map_or(bytes.len() - 1, |end| i + 2 + end);
+                if let Some(key) = parse_sequence(&bytes[i + 2..=end]) {
+                    keys.push(key);
+                }
+                i = end;
+            }
+            0x1b => keys.push(Key::Escape),
+            b'\r' | b'\n' => keys.push(Key::Enter),
+            b'\t' => keys.push(Key::Tab),
+            0x7f | 0x08 => keys.push(Key::Backspace),
+            byte @ 0x01..=0x1a => keys.push(Key::Ctrl((b'a' + byte - 1) as char)),
+            byte if byte.is_ascii_graphic() || byte == b' ' => keys.push(Key::Char(byte as char)),
+            _ => (),
+        }
+        i += 1;
+    }
+    keys
+}
+
+/// Parses the part of an escape sequence after `ESC [`
+fn parse_sequence(sequence: &[u8]) -> Option<Key> {
+    match sequence {
+        b"A" => Some(Key::Up),
+        b"B" => Some(Key::Down),
+        b"C" => Some(Key::Right),
+        b"D" => Some(Key::Left),
+        b"H" | b"1~" | b"7~" => Some(Key::Home),
+        b"F" | b"4~" | b"8~" => Some(Key::End),
+        b"3~" => Some(Key::Delete),
+        // A mouse event like `<0;12;5M`, pressing the left button
+        [b'<', rest @ ..] if rest.last() == Some(&b'M') => {
+            let rest = std::str::from_utf8(&rest[..rest.len() - 1]).ok()?;
+            let mut numbers = rest.split(';').map(|number| number.parse::<u16>().ok());
+            match (numbers.next()??, numbers.next()??, numbers.next()??) {
+                (0, column, row) => Some(Key::Click(column, row)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The squares that the user points at while choosing a move
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selection {
+    /// The square under the cursor
+    pub cursor: SQ,
+    /// The square of the piece picked up
+    pub from: Option<SQ>,
+    /// The legal destinations of the piece picked up
+    pub targets: Vec<SQ>,
+    /// The index of the chosen piece while the promotion popup is open
+    pub promotion: Option<usize>,
+}
+
+impl Selection {
+    fn new(cursor: SQ) -> Selection {
+        Selection {
+            cursor,
+            from: None,
+            targets: Vec::new(),
+            promotion: None,
+        }
+    }
+
+    /// Moves the cursor by files and ranks, staying on the board
+    fn move_cursor(&mut self, files: i8, ranks: i8) {
+        let file = (self.cursor.file_idx_of_sq() as i8 + files).clamp(0, 7);
+        let rank = (self.cursor.rank_idx_of_sq() as i8 + ranks).clamp(0, 7);
+        self.cursor = SQ((rank * 8 + file) as u8);
+    }
+
+    fn drop_piece(&mut self) {
+        self.from = None;
+        self.targets.clear();
+    }
+}
+
+/// Lets the user choose a move with the arrow keys or the mouse on the full screen.
+///
+/// Quitting gives a null move, which resigns the game.
+pub fn select_move(display: &Arc<Mutex<CliBoard>>, board: &Board) -> BitMove {
+    let moves = board.generate_moves();
+    let mut selection = Selection::new(board.king_sq(board.turn()));
+    display.lock().unwrap().set_message(
+        "Arrows and Enter or the mouse pick a piece and its square, Esc drops it, \
+         a shows attacks, h hanging pieces, p peeks, f flips and q quits.",
+    );
+
+    loop {
+        display
+            .lock()
+            .unwrap()
+            .set_selection(Some(selection.clone()));
+        for key in read_keys(TICK) {
+            // The view decides which way is up
+            let (files, ranks) = if display.lock().unwrap().is_flipped() {
+                (-1, -1)
+            } else {
+                (1, 1)
+            };
+            let target = match key {
+                Key::Up => {
+                    selection.move_cursor(0, ranks);
+                    None
+                }
+                Key::Down => {
+                    selection.move_cursor(0, -ranks);
+                    None
+                }
+                Key::Right => {
+                    selection.move_cursor(files, 0);
+                    None
+                }
+                Key::Left => {
+                    selection.move_cursor(-files, 0);
+                    None
+                }
+                Key::Enter | Key::Char(' ') => Some(selection.cursor),
+                Key::Click(column, row) => display.lock().unwrap().square_at(column, row),
+                Key::Escape | Key::Backspace => {
+                    selection.drop_piece();
+                    None
+                }
+                Key::Char('f') => {
+                    display.lock().unwrap().flip();
+                    None
+                }
+                Key::Char('a') => {
+                    // Cycle through the attacks of white, of black and none
+                    let mut display = display.lock().unwrap();
+                    let overlay = match display.overlay() {
+                        Some(Overlay::Attacks(Player::White)) => {
+                            Some(Overlay::Attacks(Player::Black))
+                        }
+                        Some(Overlay::Attacks(Player::Black)) => None,
+                        _ => Some(Overlay::Attacks(Player::White)),
+                    };
+                    display.set_overlay(overlay);
+                    None
+                }
+                Key::Char('h') => {
+                    let mut display = display.lock().unwrap();
+                    let overlay = match display.overlay() {
+                        Some(Overlay::Hanging) => None,
+                        _ => Some(Overlay::Hanging),
+                    };
+                    display.set_overlay(overlay);
+                    None
+                }
+                Key::Char('p') => {
+                    let mut display = display.lock().unwrap();
+                    display.peek();
+                    display.redraw();
+                    None
+                }
+                Key::Char('q') | Key::Eof => return BitMove::null(),
+                _ => None,
+            };
+            let sq = match target {
+                Some(sq) => sq,
+                None => continue,
+            };
+            selection.cursor = sq;
+
+            if selection.targets.contains(&sq) {
+                let from = selection.from.unwrap();
+                let candidates: Vec<BitMove> = moves
+                    .iter()
+                    .cloned()
+                    .filter(|mv| mv.get_src() == from && mv.get_dest() == sq)
+                    .collect();
+                let mv = if candidates.len() > 1 {
+                    // Only promotions share the squares
+                    match choose_promotion(display, &mut selection) {
+                        Some(piece_type) => candidates
+                            .into_iter()
+                            .find(|mv| mv.promo_piece() == piece_type),
+                        None => None,
+                    }
+                } else {
+                    candidates.into_iter().next()
+                };
+                if let Some(mv) = mv {
+                    let mut display = display.lock().unwrap();
+                    display.set_selection(None);
+                    display.set_message("");
+                    return mv;
+                }
+            } else if selection.from == Some(sq) {
+                selection.drop_piece();
+            } else if moves.iter().any(|mv| mv.get_src() == sq) {
+                selection.from = Some(sq);
+                selection.targets = moves
+                    .iter()
+                    .filter(|mv| mv.get_src() == sq)
+                    .map(|mv| mv.get_dest())
+                    .collect();
+            } else {
+                selection.drop_piece();
+            }
+        }
+    }
+}
+
+/// Opens the promotion popup until a piece is chosen or it is canceled
+fn choose_promotion(
+    display: &Arc<Mutex<CliBoard>>,
+    selection: &mut Selection,
+) -> Option<PieceType> {
+    let mut index = 0;
+    loop {
+        selection.promotion = Some(index);
+        display
+            .lock()
+            .unwrap()
+            .set_selection(Some(selection.clone()));
+        for key in read_keys(TICK) {
+            let chosen = match key {
+                Key::Left => {
+                    index = (index + PROMOTIONS.len() - 1) % PROMOTIONS.len();
+                    None
+                }
+                Key::Right => {
+                    index = (index + 1) % PROMOTIONS.len();
+                    None
+                }
+                Key::Enter | Key::Char(' ') => Some(index),
+                Key::Char(c) => PROMOTIONS
+                    .iter()
+                    .position(|piece_type| piece_type.char_lower() == c.to_ascii_lowercase()),
+                Key::Click(column, row) => display.lock().unwrap().promotion_at(column, row),
+                // Without input the move is never chosen
+                Key::Escape | Key::Backspace | Key::Eof => {
+                    selection.promotion = None;
+                    return None;
+                }
+                _ => None,
+            };
+            if let Some(chosen) = chosen {
+                selection.promotion = None;
+                return Some(PROMOTIONS[chosen]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_bytes_are_keys() {
+        assert_eq!(
+            parse_keys(b"e4 \r\n\t\x7f\x08"),
+            vec![
+                Key::Char('e'),
+                Key::Char('4'),
+                Key::Char(' '),
+                Key::Enter,
+                Key::Enter,
+                Key::Tab,
+                Key::Backspace,
+                Key::Backspace,
+            ]
+        );
+        // Control characters are letters typed with Ctrl, other bytes are ignored
+        assert_eq!(
+            parse_keys(b"\x01\x03\x1a\x00\x80"),
+            vec![Key::Ctrl('a'), Key::Ctrl('c'), Key::Ctrl('z')]
+        );
+    }
+
+    #[test]
+    fn escape_sequences_are_decoded() {
+        assert_eq!(
+            parse_keys(b"\x1b[A\x1b[B\x1b[C\x1b[D"),
+            vec![Key::Up, Key::Down, Key::Right, Key::Left]
+        );
+        // Application mode uses `ESC O` instead of `ESC [`
+        assert_eq!(parse_keys(b"\x1bOA\x1bOH"), vec![Key::Up, Key::Home]);
+        assert_eq!(
+            parse_keys(b"\x1b[H\x1b[1~\x1b[7~\x1b[F\x1b[4~\x1b[8~\x1b[3~"),
+            vec![
+                Key::Home,
+                Key::Home,
+                Key::Home,
+                Key::End,
+                Key::End,
+                Key::End,
+                Key::Delete,
+            ]
+        );
+        // Keys typed right after a sequence aren't swallowed by it
+        assert_eq!(
+            parse_keys(b"\x1b[Dx\x1b[3~y"),
+            vec![Key::Left, Key::Char('x'), Key::Delete, Key::Char('y')]
+        );
+    }
+
+    #[test]
+    fn lone_and_unknown_escapes() {
+        assert_eq!(parse_keys(b"\x1b"), vec![Key::Escape]);
+        assert_eq!(parse_keys(b"\x1bq"), vec![Key::Escape, Key::Char('q')]);
+        // Unknown sequences are skipped as a whole
+        assert_eq!(
+            parse_keys(b"\x1b[5~a\x1b[1;5Cb"),
+            vec![Key::Char('a'), Key::Char('b')]
+        );
+        // A sequence cut off by the end of the input is dropped
+        assert_eq!(parse_keys(b"a\x1b["), vec![Key::Char('a')]);
+        assert_eq!(parse_keys(b"a\x1b[12"), vec![Key::Char('a')]);
+    }
+
+    #[test]
+    fn left_clicks_are_decoded() {
+        assert_eq!(parse_keys(b"\x1b[<0;12;5M"), vec![Key::Click(12, 5)]);
+        // Releases, other buttons and broken numbers aren't clicks
+        assert_eq!(parse_keys(b"\x1b[<0;12;5m"), vec![]);
+        assert_eq!(parse_keys(b"\x1b[<2;12;5M"), vec![]);
+        assert_eq!(parse_keys(b"\x1b[<0;99999;5M"), vec![]);
+        assert_eq!(parse_keys(b"\x1b[<0;12M"), vec![]);
+        assert_eq!(
+            parse_keys(b"\x1b[<0;1;1M\x1b[<0;80;24M"),
+            vec![Key::Click(1, 1), Key::Click(80, 24)]
+        );
+    }
+}
@@ -0,0 +1,27 @@
+use pleco::{Board, Player};
+
+/// A key for each square of the black king, generated with splitmix64
+const BLACK_KING_KEYS: [u64; 64] = black_king_keys();
+
+const fn black_king_keys() -> [u64; 64] {
+    let mut keys = [0; 64];
+    let mut state: u64 = 0;
+    let mut index = 0;
+    while index < 64 {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut key = state;
+        key = (key ^ (key >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        key = (key ^ (key >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        keys[index] = key ^ (key >> 31);
+        index += 1;
+    }
+    keys
+}
+
+/// The hash key of the position.
+///
+/// pleco leaves the black king out of its zobrist keys, so positions that only differ
+/// in the square of the black king would collide. Its square is hashed in here.
+pub fn key(board: &Board) -> u64 {
+    board.zobrist() ^ BLACK_KING_KEYS[board.king_sq(Player::Black).0 as usize]
+}